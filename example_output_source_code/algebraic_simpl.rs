@@ -1,10 +1,10 @@
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList};
 
 use crate::util::*;
 
 #[pymodule]
-fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
+pub fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Buffer>()?;
 
     m.add_function(wrap_pyfunction!(main, m)?)?;
@@ -13,14 +13,14 @@ fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
 }
 
 #[pyfunction]
-fn main(py: Python<'_>) -> Py<PyAny> {
-    let mut a = 1;
-    blackbox(py, (&a));
+pub fn main(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    let mut a: i64 = convert(py, (&Num::Int(1)))?;
+    blackbox(py, (&a))?;
     let mut a1 = a;
-    blackbox(py, (&a1));
-    let mut b = 2;
-    blackbox(py, (&b));
+    blackbox(py, (&a1))?;
+    let mut b: i64 = convert(py, (&Num::Int(2)))?;
+    blackbox(py, (&b))?;
     let mut b1 = b;
-    blackbox(py, (&b1));
-    return (py.None()).to_pyany(py);
+    blackbox(py, (&b1))?;
+    return Ok((py.None()).to_pyany(py));
 }
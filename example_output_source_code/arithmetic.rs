@@ -0,0 +1,24 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::util::*;
+
+#[pymodule]
+pub fn Arithmetic(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Buffer>()?;
+
+    m.add_function(wrap_pyfunction!(quotient, m)?)?;
+    m.add_function(wrap_pyfunction!(remainder, m)?)?;
+
+    Ok(())
+}
+
+#[pyfunction]
+pub fn quotient(py: Python<'_>, mut a: Py<PyAny>, mut b: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    return Ok(((a).to_num(py)?.div((b).to_num(py)?)?).to_pyany(py));
+}
+
+#[pyfunction]
+pub fn remainder(py: Python<'_>, mut a: Py<PyAny>, mut b: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    return Ok(((a).to_num(py)?.rem((b).to_num(py)?)?).to_pyany(py));
+}
@@ -0,0 +1,105 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::util::*;
+
+#[pymodule]
+pub fn Shapes(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Buffer>()?;
+    m.add_class::<Point>()?;
+
+    m.add_function(wrap_pyfunction!(furthest, m)?)?;
+    m.add_function(wrap_pyfunction!(walk, m)?)?;
+
+    Ok(())
+}
+
+/// a point on a grid
+#[pyclass]
+pub struct Point {
+    #[pyo3(get, set)]
+    pub x: i64,
+    #[pyo3(get, set)]
+    pub y: i64,
+}
+
+#[pymethods]
+impl Point {
+    #[new]
+    pub fn __new__(_py: Python<'_>, x: i64, y: i64) -> PyResult<Self> {
+        Ok(Self { x, y })
+    }
+
+    /// squared distance from the origin
+    pub fn norm(__self: Py<Self>, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        return Ok(
+            (((__self.borrow(py).x).to_num(py)? * (__self.borrow(py).x).to_num(py)?).to_num(py)?
+                + ((__self.borrow(py).y).to_num(py)? * (__self.borrow(py).y).to_num(py)?)
+                    .to_num(py)?)
+            .to_pyany(py),
+        );
+    }
+
+    pub fn move_by(
+        __self: Py<Self>,
+        py: Python<'_>,
+        mut dx: i64,
+        mut dy: i64,
+    ) -> PyResult<Py<PyAny>> {
+        {
+            let __field_value = convert(
+                py,
+                (&((__self.borrow(py).x).to_num(py)? + (dx).to_num(py)?)),
+            )?;
+            __self.borrow_mut(py).x = __field_value;
+        }
+        {
+            let __field_value = convert(
+                py,
+                (&((__self.borrow(py).y).to_num(py)? + (dy).to_num(py)?)),
+            )?;
+            __self.borrow_mut(py).y = __field_value;
+        }
+        return Ok((__self).to_pyany(py));
+    }
+}
+
+impl_var_for_class!(Point);
+
+#[pyfunction]
+pub fn furthest(py: Python<'_>, mut points: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let mut best = get_item(py, (&points), (&Num::Int(0)))?;
+    for p in (points).to_pyany(py).bind(py).try_iter()? {
+        let mut p = p?.unbind();
+        if ((p.call_method1(py, "norm", ())?).to_num(py)?
+            > (best.call_method1(py, "norm", ())?).to_num(py)?)
+        .truthy(py)?
+        {
+            best = p;
+        }
+    }
+
+    return Ok((best).to_pyany(py));
+}
+
+#[pyfunction]
+pub fn walk(py: Python<'_>, mut steps: i64) -> PyResult<Py<PyAny>> {
+    let mut p = Py::new(
+        py,
+        Point::__new__(
+            py,
+            convert(py, (&Num::Int(0)))?,
+            convert(py, (&Num::Int(0)))?,
+        )?,
+    )?;
+    for mut i in (0i64..steps) {
+        Point::move_by(
+            p.clone_ref(py),
+            py,
+            convert(py, (&Num::Int(1)))?,
+            convert(py, (&Num::Int(2)))?,
+        )?;
+    }
+
+    return Ok((p).to_pyany(py));
+}
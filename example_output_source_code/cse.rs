@@ -1,10 +1,10 @@
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList};
 
 use crate::util::*;
 
 #[pymodule]
-fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
+pub fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Buffer>()?;
 
     m.add_function(wrap_pyfunction!(main, m)?)?;
@@ -13,17 +13,17 @@ fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
 }
 
 #[pyfunction]
-fn main(py: Python<'_>) -> Py<PyAny> {
-    let mut a = blackbox(py, (&1));
-    let mut b = blackbox(py, (&2));
-    let mut c = (a).to_usize(py) + (b).to_usize(py);
+pub fn main(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    let mut a = blackbox(py, (&Num::Int(1)))?;
+    let mut b = blackbox(py, (&Num::Int(2)))?;
+    let mut c = ((a).to_num(py)? + (b).to_num(py)?);
     let mut d = c;
-    blackbox(py, (&c));
-    blackbox(py, (&d));
+    blackbox(py, (&c))?;
+    blackbox(py, (&d))?;
     let mut e = d;
-    a = 9;
-    let mut f = (a).to_usize(py) + (b).to_usize(py);
-    blackbox(py, (&e));
-    blackbox(py, (&f));
-    return (py.None()).to_pyany(py);
+    a = Num::Int(9);
+    let mut f = ((a).to_num(py)? + (b).to_num(py)?);
+    blackbox(py, (&e))?;
+    blackbox(py, (&f))?;
+    return Ok((py.None()).to_pyany(py));
 }
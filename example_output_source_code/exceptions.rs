@@ -0,0 +1,98 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::util::*;
+
+#[pymodule]
+pub fn Errors(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Buffer>()?;
+
+    m.add_function(wrap_pyfunction!(divide, m)?)?;
+    m.add_function(wrap_pyfunction!(add, m)?)?;
+    m.add_function(wrap_pyfunction!(first_byte, m)?)?;
+
+    Ok(())
+}
+
+#[pyfunction]
+pub fn divide(py: Python<'_>, mut a: Py<PyAny>, mut b: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let __try = (|| -> PyResult<Flow<Py<PyAny>>> {
+        return Ok(Flow::Return(
+            ((a).to_num(py)?.div((b).to_num(py)?)?).to_pyany(py),
+        ));
+        Ok(Flow::Next)
+    })();
+    match __try {
+        Ok(Flow::Next) => {}
+        Ok(Flow::Return(__value)) => {
+            return Ok(__value);
+        }
+        Ok(Flow::Break | Flow::Continue) => unreachable!(),
+        Err(__err) => {
+            if __err.is_instance(py, exception(py, "ZeroDivisionError")?.bind(py)) {
+                let mut e = __err.value(py).clone().into_any().unbind();
+                return Ok(("division by zero").to_pyany(py));
+            } else {
+                return Err(__err);
+            }
+        }
+    }
+
+    return Ok((py.None()).to_pyany(py));
+}
+
+#[pyfunction]
+pub fn add(py: Python<'_>, mut a: Py<PyAny>, mut b: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let __try = (|| -> PyResult<Flow<Py<PyAny>>> {
+        return Ok(Flow::Return(
+            ((a).to_num(py)? + (b).to_num(py)?).to_pyany(py),
+        ));
+        Ok(Flow::Next)
+    })();
+    match __try {
+        Ok(Flow::Next) => {}
+        Ok(Flow::Return(__value)) => {
+            return Ok(__value);
+        }
+        Ok(Flow::Break | Flow::Continue) => unreachable!(),
+        Err(__err) => {
+            if __err.is_instance(py, exception(py, "TypeError")?.bind(py)) {
+                let mut e = __err.value(py).clone().into_any().unbind();
+                return Ok(("can\'t add").to_pyany(py));
+            } else {
+                return Err(__err);
+            }
+        }
+    }
+
+    return Ok((py.None()).to_pyany(py));
+}
+
+#[pyfunction]
+pub fn first_byte(py: Python<'_>, mut size: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let mut buffer = allocate(py, (&size))?;
+    free(py, (&buffer))?;
+    let __try = (|| -> PyResult<Flow<Py<PyAny>>> {
+        return Ok(Flow::Return(
+            (buffer.get_item(py, (&Num::Int(0)))?).to_pyany(py),
+        ));
+        Ok(Flow::Next)
+    })();
+    match __try {
+        Ok(Flow::Next) => {}
+        Ok(Flow::Return(__value)) => {
+            return Ok(__value);
+        }
+        Ok(Flow::Break | Flow::Continue) => unreachable!(),
+        Err(__err) => {
+            if __err.is_instance(py, exception(py, "ValueError")?.bind(py)) {
+                let mut e = __err.value(py).clone().into_any().unbind();
+                return Ok(("freed").to_pyany(py));
+            } else {
+                return Err(__err);
+            }
+        }
+    }
+
+    return Ok((py.None()).to_pyany(py));
+}
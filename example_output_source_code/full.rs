@@ -1,10 +1,10 @@
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList};
 
 use crate::util::*;
 
 #[pymodule]
-fn DataManagement(m: &Bound<'_, PyModule>) -> PyResult<()> {
+pub fn DataManagement(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Buffer>()?;
 
     m.add_function(wrap_pyfunction!(allocateMemory, m)?)?;
@@ -16,37 +16,35 @@ fn DataManagement(m: &Bound<'_, PyModule>) -> PyResult<()> {
 }
 
 #[pyfunction]
-fn allocateMemory(py: Python<'_>, size: Py<PyAny>) -> Py<PyAny> {
-    let mut buffer = allocate(py, (&size));
-    return (buffer).to_pyany(py);
+pub fn allocateMemory(py: Python<'_>, mut size: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let mut buffer = allocate(py, (&size))?;
+    return Ok((buffer).to_pyany(py));
 }
 
 #[pyfunction]
-fn freeMemory(py: Python<'_>, ptr: Py<PyAny>) -> Py<PyAny> {
-    free(py, (&ptr));
-    return (10).to_pyany(py);
+pub fn freeMemory(py: Python<'_>, mut ptr: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    free(py, (&ptr))?;
+    return Ok((Num::Int(10)).to_pyany(py));
 }
 
 #[pyfunction]
-fn optimizeMe(py: Python<'_>) -> Py<PyAny> {
-    let mut sum = 3;
-    allocate(py, (&sum));
-    return (py.None()).to_pyany(py);
+pub fn optimizeMe(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    let mut sum = Num::Int(3);
+    allocate(py, (&sum))?;
+    return Ok((py.None()).to_pyany(py));
 }
 
 #[pyfunction]
-fn processStream(py: Python<'_>, streamSize: Py<PyAny>) -> Py<PyAny> {
-    let mut blocksize = 10;
-    let mut streamPtr = allocate(py, (&streamSize));
-    let mut blocks = PyList::new(py, Vec::<Buffer>::new()).unwrap().unbind();
-    let mut i = 0;
-    while (i).to_usize(py) < (streamSize).to_usize(py) {
-        let mut blockPtr = (streamPtr).to_buffer(py).borrow(py, (&blocksize), (&i));
-        blocks
-            .call_method(py, "append", ((&blockPtr),), None)
-            .unwrap();
-        i = (i).to_usize(py) + (blocksize).to_usize(py);
+pub fn processStream(py: Python<'_>, mut streamSize: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let mut blocksize = Num::Int(10);
+    let mut streamPtr = allocate(py, (&streamSize))?;
+    let mut blocks = PyList::new(py, Vec::<Buffer>::new())?.unbind();
+    let mut i = Num::Int(0);
+    while ((i).to_num(py)? < (streamSize).to_num(py)?).truthy(py)? {
+        let mut blockPtr = (streamPtr).to_buffer(py)?.borrow(py, (&blocksize), (&i))?;
+        blocks.call_method1(py, "append", ((blockPtr).to_pyany(py),))?;
+        i = ((i).to_num(py)? + (blocksize).to_num(py)?);
     }
 
-    return (blocks).to_pyany(py);
+    return Ok((blocks).to_pyany(py));
 }
@@ -0,0 +1,118 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::util::*;
+
+#[pymodule]
+pub fn Lists(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Buffer>()?;
+
+    m.add_function(wrap_pyfunction!(ints, m)?)?;
+    m.add_function(wrap_pyfunction!(floats, m)?)?;
+    m.add_function(wrap_pyfunction!(mixed, m)?)?;
+    m.add_function(wrap_pyfunction!(nested, m)?)?;
+    m.add_function(wrap_pyfunction!(first_of_each, m)?)?;
+
+    Ok(())
+}
+
+#[pyfunction]
+pub fn ints(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    return Ok((PyList::new(py, vec![1i64, 2i64, 3i64])?.unbind()).to_pyany(py));
+}
+
+#[pyfunction]
+pub fn floats(py: Python<'_>, mut a: f64) -> PyResult<Py<PyAny>> {
+    return Ok((PyList::new(
+        py,
+        vec![
+            (a).to_pyany(py),
+            (Num::Float(2.5)).to_pyany(py),
+            (Num::Float(-1.0)).to_pyany(py),
+        ],
+    )?
+    .unbind())
+    .to_pyany(py));
+}
+
+#[pyfunction]
+pub fn mixed(py: Python<'_>, mut a: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    return Ok((PyList::new(
+        py,
+        vec![
+            (Num::Int(1)).to_pyany(py),
+            ("two").to_pyany(py),
+            (a).to_pyany(py),
+            (py.None()).to_pyany(py),
+        ],
+    )?
+    .unbind())
+    .to_pyany(py));
+}
+
+#[pyfunction]
+pub fn nested(py: Python<'_>, mut n: i64) -> PyResult<Py<PyAny>> {
+    return Ok((PyList::new(
+        py,
+        vec![
+            PyList::new(
+                py,
+                vec![
+                    (n).to_pyany(py),
+                    ((n).to_num(py)? + (Num::Int(1)).to_num(py)?).to_pyany(py),
+                ],
+            )?
+            .unbind(),
+            PyList::new(py, Vec::<Buffer>::new())?.unbind(),
+            PyList::new(
+                py,
+                vec![PyList::new(
+                    py,
+                    vec![((n).to_num(py)? * (Num::Int(2)).to_num(py)?).to_pyany(py)],
+                )?
+                .unbind()],
+            )?
+            .unbind(),
+        ],
+    )?
+    .unbind())
+    .to_pyany(py));
+}
+
+#[pyfunction]
+pub fn first_of_each(py: Python<'_>, mut n: i64) -> PyResult<Py<PyAny>> {
+    let mut rows = PyList::new(
+        py,
+        vec![
+            PyList::new(py, vec![(n).to_pyany(py), (Num::Int(1)).to_pyany(py)])?.unbind(),
+            PyList::new(
+                py,
+                vec![
+                    ((n).to_num(py)? + (Num::Int(1)).to_num(py)?).to_pyany(py),
+                    (Num::Int(2)).to_pyany(py),
+                ],
+            )?
+            .unbind(),
+        ],
+    )?
+    .unbind();
+    return Ok((PyList::new(
+        py,
+        vec![
+            (get_item(
+                py,
+                (&get_item(py, (&rows), (&Num::Int(0)))?),
+                (&Num::Int(0)),
+            )?)
+            .to_pyany(py),
+            (get_item(
+                py,
+                (&get_item(py, (&rows), (&Num::Int(1)))?),
+                (&Num::Int(0)),
+            )?)
+            .to_pyany(py),
+        ],
+    )?
+    .unbind())
+    .to_pyany(py));
+}
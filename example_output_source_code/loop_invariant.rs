@@ -1,10 +1,10 @@
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList};
 
 use crate::util::*;
 
 #[pymodule]
-fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
+pub fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Buffer>()?;
 
     m.add_function(wrap_pyfunction!(main, m)?)?;
@@ -13,24 +13,24 @@ fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
 }
 
 #[pyfunction]
-fn main(py: Python<'_>) -> Py<PyAny> {
-    let mut a = blackbox(py, (&1));
-    let mut b = blackbox(py, (&2));
-    let mut acc = 0;
-    let mut __temp_0 = (a).to_usize(py) * (b).to_usize(py);
-    let mut i = 0;
-    while (i).to_usize(py) < (10).to_usize(py) {
-        acc = (acc).to_usize(py) + (__temp_0).to_usize(py);
-        i = (i).to_usize(py) + (1).to_usize(py);
+pub fn main(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    let mut a: i64 = convert(py, (&Num::Int(1)))?;
+    let mut b: i64 = convert(py, (&Num::Int(2)))?;
+    let mut acc = Num::Int(0);
+    let mut __temp_0 = ((a).to_num(py)? * (b).to_num(py)?);
+    let mut i = Num::Int(0);
+    while ((i).to_num(py)? < (Num::Int(10)).to_num(py)?).truthy(py)? {
+        acc = ((acc).to_num(py)? + (__temp_0).to_num(py)?);
+        i = ((i).to_num(py)? + (Num::Int(1)).to_num(py)?);
     }
 
-    let mut i = 0;
-    while (i).to_usize(py) < (10).to_usize(py) {
-        a = (a).to_usize(py) + (1).to_usize(py);
-        acc = (acc).to_usize(py) + ((a).to_usize(py) * (b).to_usize(py)).to_usize(py);
-        i = (i).to_usize(py) + (1).to_usize(py);
+    let mut i = Num::Int(0);
+    while ((i).to_num(py)? < (Num::Int(10)).to_num(py)?).truthy(py)? {
+        a = convert(py, (&((a).to_num(py)? + (Num::Int(1)).to_num(py)?)))?;
+        acc = ((acc).to_num(py)? + ((a).to_num(py)? * (b).to_num(py)?).to_num(py)?);
+        i = ((i).to_num(py)? + (Num::Int(1)).to_num(py)?);
     }
 
-    blackbox(py, (&acc));
-    return (py.None()).to_pyany(py);
+    blackbox(py, (&acc))?;
+    return Ok((py.None()).to_pyany(py));
 }
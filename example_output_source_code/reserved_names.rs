@@ -0,0 +1,71 @@
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::util::*;
+
+#[pymodule]
+pub fn Reserved(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Buffer>()?;
+    m.add_class::<__user_Buffer>()?;
+
+    m.add_function(wrap_pyfunction!(__user_range, m)?)?;
+    m.add_function(wrap_pyfunction!(__user_lambda, m)?)?;
+
+    Ok(())
+}
+
+#[pyclass(name = "Buffer")]
+pub struct __user_Buffer {
+    #[pyo3(get, set, name = "m")]
+    pub __user_m: i64,
+}
+
+#[pymethods]
+impl __user_Buffer {
+    #[new]
+    pub fn __new__(_py: Python<'_>, __user_m: i64) -> PyResult<Self> {
+        Ok(Self { __user_m })
+    }
+
+    #[pyo3(name = "py")]
+    pub fn __user_py(
+        __self: Py<Self>,
+        py: Python<'_>,
+        mut value: Py<PyAny>,
+    ) -> PyResult<Py<PyAny>> {
+        return Ok(((__self.borrow(py).__user_m).to_num(py)? + (value).to_num(py)?).to_pyany(py));
+    }
+}
+
+impl_var_for_class!(__user_Buffer);
+
+#[pyfunction]
+#[pyo3(name = "range")]
+pub fn __user_range(
+    py: Python<'_>,
+    mut __user_py: Py<PyAny>,
+    mut __user_m: Py<PyAny>,
+) -> PyResult<Py<PyAny>> {
+    let mut __user___dict = ((__user_py).to_num(py)? + (__user_m).to_num(py)?);
+    let mut __user_Ok = ((__user___dict).to_num(py)? * (Num::Int(2)).to_num(py)?);
+    let mut b = Py::new(py, __user_Buffer::__new__(py, convert(py, (&__user_Ok))?)?)?;
+    return Ok(
+        (__user_Buffer::__user_py(b.clone_ref(py), py, convert(py, (&Num::Int(1)))?)?).to_pyany(py),
+    );
+}
+
+#[pyfunction]
+#[pyo3(name = "lambda")]
+pub fn __user_lambda(py: Python<'_>, mut value: Py<PyAny>) -> PyResult<Py<PyAny>> {
+    let mut f = {
+        let value = value.clone_ref(py);
+        lambda(py, move |__args, __kwargs| {
+            let py = __args.py();
+            let mut value = value.clone_ref(py);
+            let [mut x] = lambda_args::<1>(__args, __kwargs)?;
+
+            return Ok(((x).to_num(py)? + (value).to_num(py)?).to_pyany(py));
+        })?
+    };
+    return Ok(((f).to_pyany(py).call1(py, ((Num::Int(2)).to_pyany(py),))?).to_pyany(py));
+}
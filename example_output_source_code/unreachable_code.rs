@@ -1,10 +1,10 @@
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList};
 
 use crate::util::*;
 
 #[pymodule]
-fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
+pub fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Buffer>()?;
 
     m.add_function(wrap_pyfunction!(main, m)?)?;
@@ -13,8 +13,8 @@ fn ExampleRegion(m: &Bound<'_, PyModule>) -> PyResult<()> {
 }
 
 #[pyfunction]
-fn main(py: Python<'_>) -> Py<PyAny> {
-    let mut a = blackbox(py, (&1));
-    blackbox(py, (&a));
-    return (a).to_pyany(py);
+pub fn main(py: Python<'_>) -> PyResult<Py<PyAny>> {
+    let mut a = blackbox(py, (&Num::Int(1)))?;
+    blackbox(py, (&a))?;
+    return Ok((a).to_pyany(py));
 }
@@ -1,2 +1,4 @@
-pub mod auto;
+#[macro_use]
 pub mod util;
+
+pub mod auto;
@@ -1,20 +1,287 @@
 use pyo3::{
-    exceptions::PyIndexError, prelude::*, types::PyList, types::PyListMethods, IntoPyObjectExt,
+    exceptions::{PyIndexError, PyOverflowError, PyTypeError, PyValueError, PyZeroDivisionError},
+    prelude::*,
+    sync::GILOnceCell,
+    types::PyList,
+    types::PyListMethods,
+    types::{PyCFunction, PyDict, PyDictMethods, PyTuple},
+    IntoPyObjectExt,
 };
 use std::sync::{Arc, RwLock};
 
-pub fn allocate(py: Python<'_>, size: &impl Var) -> Buffer {
-    let size = size.to_usize(py);
-    Buffer::new(size)
+pub fn allocate(py: Python<'_>, size: &impl Var) -> PyResult<Buffer> {
+    let size = size.to_usize(py)?;
+    Ok(Buffer::new(size))
 }
 
-pub fn free(py: Python<'_>, buffer: &impl Var) {
-    let mut buffer = buffer.to_buffer(py);
-    buffer.free();
+pub fn free(py: Python<'_>, buffer: &impl Var) -> PyResult<()> {
+    let mut buffer = buffer.to_buffer(py)?;
+    buffer.free()
 }
 
-pub fn blackbox<'a, V: Var>(_py: Python<'_>, v: &'a V) -> &'a V {
-    std::hint::black_box(v)
+pub fn blackbox<'a, V: Var>(_py: Python<'_>, v: &'a V) -> PyResult<&'a V> {
+    Ok(std::hint::black_box(v))
+}
+
+/// convert a value to the rust type of a type annotation, e.g. `i64` for
+/// `let x: int = ...`. like pyo3's argument extraction, the wrong type is a
+/// TypeError.
+pub fn convert<'py, T: FromPyObject<'py>>(py: Python<'py>, value: &impl Var) -> PyResult<T> {
+    value.to_pyany(py).into_bound(py).extract()
+}
+
+/// check that every item of a `list<T>` parameter is a T. pyo3 only checks
+/// that it's a list.
+pub fn check_list_items<'py, T: FromPyObject<'py>>(
+    py: Python<'py>,
+    list: &Py<PyList>,
+) -> PyResult<()> {
+    for item in list.bind(py).iter() {
+        item.extract::<T>()?;
+    }
+    Ok(())
+}
+
+/// check that every key and value of a `dict<K, V>` parameter is a K and a
+/// V. pyo3 only checks that it's a dict.
+pub fn check_dict_items<'py, K: FromPyObject<'py>, V: FromPyObject<'py>>(
+    py: Python<'py>,
+    dict: &Py<PyDict>,
+) -> PyResult<()> {
+    for (key, value) in dict.bind(py).iter() {
+        key.extract::<K>()?;
+        value.extract::<V>()?;
+    }
+    Ok(())
+}
+
+/// copying a value, which for python objects needs the GIL. `Py` has its
+/// own `clone_ref`, this is for everything else.
+pub trait CloneRef {
+    fn clone_ref(&self, py: Python<'_>) -> Self;
+}
+
+macro_rules! impl_clone_ref {
+    ($($t:ty),*) => {
+        $(impl CloneRef for $t {
+            fn clone_ref(&self, _py: Python<'_>) -> Self {
+                self.clone()
+            }
+        })*
+    };
+}
+
+impl_clone_ref!(Num, Buffer, i64, f64, bool, String);
+
+/// `range(start, stop, step)` as a rust iterator. like python, the arguments
+/// have to be ints and the step can't be zero.
+pub fn range(
+    py: Python<'_>,
+    start: &impl Var,
+    stop: &impl Var,
+    step: &impl Var,
+) -> PyResult<impl Iterator<Item = i64>> {
+    let start = start.to_num(py)?.to_int()?;
+    let stop = stop.to_num(py)?.to_int()?;
+    let step = step.to_num(py)?.to_int()?;
+    if step == 0 {
+        return Err(PyValueError::new_err("range() arg 3 must not be zero"));
+    }
+
+    // the number of ints in the range, rounded up
+    let len = if step > 0 {
+        (stop - start + step - 1) / step
+    } else {
+        (start - stop - step - 1) / -step
+    };
+    Ok((0..len.max(0)).map(move |i| start + i * step))
+}
+
+/// the python exception class with this name, e.g. `ValueError`. `Error` is
+/// any exception, like python's `Exception`.
+pub fn exception(py: Python<'_>, name: &str) -> PyResult<Py<PyAny>> {
+    let name = if name == "Error" { "Exception" } else { name };
+    Ok(py.import("builtins")?.getattr(name)?.unbind())
+}
+
+/// `raise value`. like python, the value can be an exception or an exception
+/// class, anything else raises a TypeError instead.
+pub fn raise(py: Python<'_>, value: &impl Var) -> PyErr {
+    PyErr::from_value(value.to_pyany(py).into_bound(py))
+}
+
+/// how a try block finished. it runs in a closure, so a `return`, `break` or
+/// `continue` in it is done after the closure returns.
+pub enum Flow<T> {
+    Next,
+    Return(T),
+    Break,
+    Continue,
+}
+
+/// a python function that runs a rust closure, for a lambda
+pub fn lambda<F>(py: Python<'_>, f: F) -> PyResult<Py<PyAny>>
+where
+    F: Fn(&Bound<'_, PyTuple>, Option<&Bound<'_, PyDict>>) -> PyResult<Py<PyAny>> + Send + 'static,
+{
+    Ok(PyCFunction::new_closure(py, None, None, f)?
+        .into_any()
+        .unbind())
+}
+
+/// the arguments of a call to a lambda, which takes exactly `N` of them and
+/// no keyword arguments
+pub fn lambda_args<const N: usize>(
+    args: &Bound<'_, PyTuple>,
+    kwargs: Option<&Bound<'_, PyDict>>,
+) -> PyResult<[Py<PyAny>; N]> {
+    if kwargs.is_some_and(|kwargs| !kwargs.is_empty()) {
+        return Err(PyTypeError::new_err(
+            "lambda() got an unexpected keyword argument",
+        ));
+    }
+
+    let given = args.len();
+    let args = args.iter().map(|arg| arg.unbind()).collect::<Vec<_>>();
+    args.try_into().map_err(|_| {
+        PyTypeError::new_err(format!(
+            "lambda() takes {N} positional arguments but {given} were given"
+        ))
+    })
+}
+
+/// where a region keeps its globals: the attributes of its python module, so
+/// python can use them too. a region that python doesn't import gets a module
+/// of its own the first time a global is used.
+pub struct Globals {
+    name: &'static str,
+    init: fn(&Bound<'_, PyModule>) -> PyResult<()>,
+    module: GILOnceCell<Py<PyModule>>,
+}
+
+impl Globals {
+    /// `init` sets the globals of a module, in order
+    pub const fn new(name: &'static str, init: fn(&Bound<'_, PyModule>) -> PyResult<()>) -> Self {
+        Self {
+            name,
+            init,
+            module: GILOnceCell::new(),
+        }
+    }
+
+    /// keep the globals in the region's python module, and set them
+    pub fn init(&self, m: &Bound<'_, PyModule>) -> PyResult<()> {
+        let _ = self.module.set(m.py(), m.clone().unbind());
+        (self.init)(m)
+    }
+
+    fn module<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyModule>> {
+        if let Some(m) = self.module.get(py) {
+            return Ok(m.bind(py).clone());
+        }
+
+        // the module is kept before the globals are set, so their values
+        // can use the ones before them
+        let m = PyModule::new(py, self.name)?;
+        let _ = self.module.set(py, m.clone().unbind());
+        (self.init)(&m)?;
+        Ok(m)
+    }
+
+    /// the value of a global
+    pub fn get(&self, py: Python<'_>, name: &str) -> PyResult<Py<PyAny>> {
+        Ok(self.module(py)?.getattr(name)?.unbind())
+    }
+
+    /// `global = value`
+    pub fn set(&self, py: Python<'_>, name: &str, value: &impl Var) -> PyResult<()> {
+        self.module(py)?.setattr(name, value.to_pyany(py))
+    }
+}
+
+/// a python module, or an attribute of one, that a region imports. it's
+/// imported the first time it's used.
+pub struct PyImport {
+    module: &'static str,
+    attribute: Option<&'static str>,
+    value: GILOnceCell<Py<PyAny>>,
+}
+
+impl PyImport {
+    /// `import module`, or `from module import attribute`
+    pub const fn new(module: &'static str, attribute: Option<&'static str>) -> Self {
+        Self {
+            module,
+            attribute,
+            value: GILOnceCell::new(),
+        }
+    }
+
+    /// the imported module or attribute
+    pub fn get(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let value = self.value.get_or_try_init(py, || {
+            let module = py.import(self.module)?;
+            let Some(attribute) = self.attribute else {
+                return Ok(module.into_any().unbind());
+            };
+
+            // like python, the attribute can be a submodule that hasn't been
+            // imported yet
+            match module.getattr(attribute) {
+                Ok(value) => Ok(value.unbind()),
+                Err(err) => match py.import(format!("{}.{attribute}", self.module)) {
+                    Ok(submodule) => Ok(submodule.into_any().unbind()),
+                    Err(_) => Err(err),
+                },
+            }
+        })?;
+        Ok(value.clone_ref(py))
+    }
+}
+
+/// the keyword arguments of a call to python
+pub fn kwargs<'py, const N: usize>(
+    py: Python<'py>,
+    args: [(&str, Py<PyAny>); N],
+) -> PyResult<Bound<'py, PyDict>> {
+    let kwargs = PyDict::new(py);
+    for (key, value) in args {
+        kwargs.set_item(key, value)?;
+    }
+    Ok(kwargs)
+}
+
+/// `value[index]` for any python value, using its `__getitem__`
+pub fn get_item(py: Python<'_>, value: &impl Var, index: &impl Var) -> PyResult<Py<PyAny>> {
+    let value = value.to_pyany(py);
+    let item = value.bind(py).get_item(index.to_pyany(py))?;
+    Ok(item.unbind())
+}
+
+/// `value[index] = item` for any python value, using its `__setitem__`
+pub fn set_item(
+    py: Python<'_>,
+    value: &impl Var,
+    index: &impl Var,
+    item: &impl Var,
+) -> PyResult<()> {
+    let value = value.to_pyany(py);
+    value
+        .bind(py)
+        .set_item(index.to_pyany(py), item.to_pyany(py))
+}
+
+/// `value.field` for any python value, using its `__getattr__`
+pub fn getattr(py: Python<'_>, value: &impl Var, field: &str) -> PyResult<Py<PyAny>> {
+    let value = value.to_pyany(py);
+    let item = value.bind(py).getattr(field)?;
+    Ok(item.unbind())
+}
+
+/// `value.field = item` for any python value, using its `__setattr__`
+pub fn setattr(py: Python<'_>, value: &impl Var, field: &str, item: &impl Var) -> PyResult<()> {
+    let value = value.to_pyany(py);
+    value.bind(py).setattr(field, item.to_pyany(py))
 }
 
 #[pyclass]
@@ -30,61 +297,105 @@ impl Buffer {
         }
     }
 
-    pub fn free(&mut self) {
-        let mut data = self
-            .data
-            .try_write()
-            .expect("Can't free buffer while borrowed");
+    pub fn free(&mut self) -> PyResult<()> {
+        let Ok(mut data) = self.data.try_write() else {
+            return Err(PyValueError::new_err("Can't free buffer while borrowed"));
+        };
 
         let Some(data_vec) = data.as_mut() else {
             // already freed, do nothing
-            return;
+            return Ok(());
         };
 
         // check no bytes are borrowed
-        for byte in data_vec.iter() {
-            if byte.borrowed {
-                panic!("Can't free buffer while borrowed");
-            }
+        if data_vec.iter().any(|byte| byte.borrowed) {
+            return Err(PyValueError::new_err("Can't free buffer while borrowed"));
         }
 
         *data = None;
+        Ok(())
     }
 
-    pub fn borrow(&self, py: Python<'_>, size: &impl Var, index: &impl Var) -> Py<PyList> {
-        let size = size.to_usize(py);
-        let index = index.to_usize(py);
+    pub fn borrow(
+        &self,
+        py: Python<'_>,
+        size: &impl Var,
+        index: &impl Var,
+    ) -> PyResult<Py<PyList>> {
+        let size = size.to_usize(py)?;
+        let index = index.to_usize(py)?;
 
         let mut data = self.data.write().unwrap();
-        let data = data.as_mut().unwrap();
+        let data = data.as_mut().ok_or_else(freed)?;
+        let Some(bytes) = data.get_mut(index..index + size) else {
+            return Err(PyIndexError::new_err("Index out of bounds"));
+        };
 
         let mut borrowed_data = vec![];
-        for i in index..index + size {
-            data[i].borrow();
-            borrowed_data.push(data[i].data);
+        for byte in bytes {
+            byte.borrow();
+            borrowed_data.push(byte.data);
         }
 
-        PyList::new(py, borrowed_data).unwrap().into()
+        Ok(PyList::new(py, borrowed_data)?.into())
     }
 
-    pub fn borrowMut(&self, py: Python<'_>, size: &impl Var, index: &impl Var) -> Py<PyList> {
-        let size = size.to_usize(py);
-        let index = index.to_usize(py);
+    pub fn borrowMut(
+        &self,
+        py: Python<'_>,
+        size: &impl Var,
+        index: &impl Var,
+    ) -> PyResult<Py<PyList>> {
+        let size = size.to_usize(py)?;
+        let index = index.to_usize(py)?;
 
         let mut data = self.data.write().unwrap();
-        let data = data.as_mut().unwrap();
+        let data = data.as_mut().ok_or_else(freed)?;
+        let Some(bytes) = data.get_mut(index..index + size) else {
+            return Err(PyIndexError::new_err("Index out of bounds"));
+        };
+        if bytes.iter().any(|byte| byte.borrowed) {
+            return Err(PyValueError::new_err("Can't borrow mutably while borrowed"));
+        }
 
         let mut borrowed_data = vec![];
-        for i in index..index + size {
-            if data[i].borrowed {
-                panic!("Can't borrow mutably while borrowed");
-            }
-
-            data[i].borrow();
-            borrowed_data.push(data[i].data);
+        for byte in bytes {
+            byte.borrow();
+            borrowed_data.push(byte.data);
         }
 
-        PyList::new(py, borrowed_data).unwrap().into()
+        Ok(PyList::new(py, borrowed_data)?.into())
+    }
+
+    /// `buffer[index]`, without going through python
+    pub fn get_item(&self, py: Python<'_>, index: &impl Var) -> PyResult<Num> {
+        let byte = self.__getitem__(index.to_num(py)?.to_index()?)?;
+        Ok(Num::Int(byte as i64))
+    }
+
+    /// the bytes of the buffer, for looping over it without going through
+    /// python. each byte is read when it's needed, so the loop can still
+    /// change the buffer.
+    pub fn bytes(&self) -> impl Iterator<Item = i64> {
+        let buffer = self.clone();
+        (0..).map_while(move |index| {
+            let guard = buffer.data.read().unwrap();
+            let byte = guard.as_ref().unwrap().get(index)?;
+            Some(byte.data as i64)
+        })
+    }
+
+    /// `buffer[index] = value`, without going through python
+    pub fn set_item(&mut self, py: Python<'_>, index: &impl Var, value: &impl Var) -> PyResult<()> {
+        let byte = match value.to_num(py)? {
+            Num::Int(i) => u8::try_from(i).ok(),
+            Num::Float(_) => None,
+        };
+        let Some(byte) = byte else {
+            return Err(PyValueError::new_err("byte must be in range(0, 256)"));
+        };
+
+        self.__setitem__(index.to_num(py)?.to_index()?, byte)
     }
 }
 
@@ -92,7 +403,7 @@ impl Buffer {
 impl Buffer {
     pub fn __getitem__(&self, index: isize) -> PyResult<u8> {
         let guard = self.data.read().unwrap();
-        let data = guard.as_ref().unwrap();
+        let data = guard.as_ref().ok_or_else(freed)?;
 
         if index < 0 || index as usize >= data.len() {
             return Err(PyErr::new::<PyIndexError, _>("Index out of bounds"));
@@ -103,7 +414,7 @@ impl Buffer {
 
     pub fn __setitem__(&mut self, index: isize, value: u8) -> PyResult<()> {
         let mut guard = self.data.write().unwrap();
-        let data = guard.as_mut().unwrap();
+        let data = guard.as_mut().ok_or_else(freed)?;
 
         if index < 0 || index as usize >= data.len() {
             return Err(PyErr::new::<PyIndexError, _>("Index out of bounds"));
@@ -136,6 +447,11 @@ impl Buffer {
     }
 }
 
+/// the error for using a buffer after it was freed
+fn freed() -> PyErr {
+    PyValueError::new_err("Can't use buffer after it was freed")
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Byte {
     data: u8,
@@ -175,11 +491,22 @@ impl AsMut<u8> for Byte {
     }
 }
 
+/// a value in the generated code. converting it to a type it can't be is a
+/// TypeError, like in python.
 pub trait Var {
     fn to_pyany(&self, py: Python<'_>) -> Py<PyAny>;
-    fn to_pylist<T>(&self, py: Python<'_>) -> Py<PyList>;
-    fn to_buffer(&self, py: Python<'_>) -> Buffer;
-    fn to_usize(&self, py: Python<'_>) -> usize;
+    fn to_pylist<T>(&self, py: Python<'_>) -> PyResult<Py<PyList>>;
+    fn to_pydict(&self, py: Python<'_>) -> PyResult<Py<PyDict>>;
+    fn to_buffer(&self, py: Python<'_>) -> PyResult<Buffer>;
+    fn to_usize(&self, py: Python<'_>) -> PyResult<usize>;
+    fn to_num(&self, py: Python<'_>) -> PyResult<Num>;
+    /// whether the value is true in a condition, using python's rules
+    fn truthy(&self, py: Python<'_>) -> PyResult<bool>;
+}
+
+/// the TypeError for converting a value to a type it can't be
+pub fn cant_convert(from: &str, to: &str) -> PyErr {
+    PyTypeError::new_err(format!("Can't convert {from} to {to}"))
 }
 
 impl Var for Py<PyAny> {
@@ -187,17 +514,39 @@ impl Var for Py<PyAny> {
         self.clone_ref(py)
     }
 
-    fn to_pylist<T>(&self, py: Python<'_>) -> Py<PyList> {
-        let list: &Bound<'_, PyList> = self.downcast_bound(py).unwrap();
-        list.clone().unbind()
+    fn to_pylist<T>(&self, py: Python<'_>) -> PyResult<Py<PyList>> {
+        let list: &Bound<'_, PyList> = self.downcast_bound(py)?;
+        Ok(list.clone().unbind())
+    }
+
+    fn to_pydict(&self, py: Python<'_>) -> PyResult<Py<PyDict>> {
+        let dict: &Bound<'_, PyDict> = self.downcast_bound(py)?;
+        Ok(dict.clone().unbind())
+    }
+
+    fn to_buffer(&self, py: Python<'_>) -> PyResult<Buffer> {
+        self.extract::<Buffer>(py)
     }
 
-    fn to_buffer(&self, py: Python<'_>) -> Buffer {
-        self.extract::<Buffer>(py).unwrap()
+    fn to_usize(&self, py: Python<'_>) -> PyResult<usize> {
+        self.extract::<usize>(py)
+    }
+
+    fn to_num(&self, py: Python<'_>) -> PyResult<Num> {
+        if let Ok(i) = self.extract::<i64>(py) {
+            return Ok(Num::Int(i));
+        }
+        match self.extract::<f64>(py) {
+            Ok(f) => Ok(Num::Float(f)),
+            Err(_) => Err(cant_convert(
+                &self.bind(py).get_type().name()?.to_string(),
+                "a number",
+            )),
+        }
     }
 
-    fn to_usize(&self, py: Python<'_>) -> usize {
-        self.extract::<usize>(py).unwrap()
+    fn truthy(&self, py: Python<'_>) -> PyResult<bool> {
+        self.bind(py).is_truthy()
     }
 }
 
@@ -206,32 +555,43 @@ impl Var for Py<PyList> {
         self.as_any().clone_ref(py)
     }
 
-    fn to_pylist<T>(&self, py: Python<'_>) -> Py<PyList> {
-        self.clone_ref(py)
+    fn to_pylist<T>(&self, py: Python<'_>) -> PyResult<Py<PyList>> {
+        Ok(self.clone_ref(py))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("PyList", "PyDict"))
     }
 
-    fn to_buffer(&self, py: Python<'_>) -> Buffer {
+    fn to_buffer(&self, py: Python<'_>) -> PyResult<Buffer> {
         let mut data = Vec::<Byte>::new();
-        self.bind(py).iter().for_each(|byte| {
+        for byte in self.bind(py).iter() {
             // try extracting usize, byte, and buffer
             if let Ok(byte) = byte.extract::<u8>() {
                 data.push(Byte::new(byte));
             } else if let Ok(buffer) = byte.extract::<Buffer>() {
                 let guard = buffer.data.read().unwrap();
-                let buffer_data = guard.as_ref().unwrap();
-                data.extend_from_slice(buffer_data);
+                data.extend_from_slice(guard.as_ref().ok_or_else(freed)?);
             } else {
-                panic!("Can't convert PyList to Buffer");
+                return Err(cant_convert("PyList", "Buffer"));
             }
-        });
+        }
 
-        Buffer {
+        Ok(Buffer {
             data: Arc::new(RwLock::new(Some(data))),
-        }
+        })
+    }
+
+    fn to_usize(&self, py: Python<'_>) -> PyResult<usize> {
+        self.extract::<usize>(py)
+    }
+
+    fn to_num(&self, _py: Python<'_>) -> PyResult<Num> {
+        Err(cant_convert("PyList", "a number"))
     }
 
-    fn to_usize(&self, py: Python<'_>) -> usize {
-        self.extract::<usize>(py).unwrap()
+    fn truthy(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(!self.bind(py).is_empty())
     }
 }
 
@@ -240,37 +600,420 @@ impl Var for Buffer {
         self.clone().into_py_any(py).unwrap()
     }
 
-    fn to_pylist<T>(&self, py: Python<'_>) -> Py<PyList> {
-        let guard = self.data.read();
-        let data = guard.as_ref().unwrap().as_ref().unwrap();
-        PyList::new(py, data.iter().map(|byte| byte.data).collect::<Vec<u8>>())
-            .unwrap()
-            .unbind()
+    fn to_pylist<T>(&self, py: Python<'_>) -> PyResult<Py<PyList>> {
+        let guard = self.data.read().unwrap();
+        let data = guard.as_ref().ok_or_else(freed)?;
+        Ok(PyList::new(py, data.iter().map(|byte| byte.data))?.unbind())
     }
 
-    fn to_buffer(&self, _: Python<'_>) -> Buffer {
-        self.clone()
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("Buffer", "PyDict"))
     }
 
-    fn to_usize(&self, _: Python<'_>) -> usize {
-        self.data.read().unwrap().as_ref().unwrap().len()
+    fn to_buffer(&self, _: Python<'_>) -> PyResult<Buffer> {
+        Ok(self.clone())
+    }
+
+    fn to_usize(&self, _: Python<'_>) -> PyResult<usize> {
+        Ok(self.data.read().unwrap().as_ref().ok_or_else(freed)?.len())
+    }
+
+    fn to_num(&self, py: Python<'_>) -> PyResult<Num> {
+        Ok(Num::Int(self.to_usize(py)? as i64))
+    }
+
+    fn truthy(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(self.to_usize(py)? != 0)
     }
 }
 
-impl Var for usize {
+impl Var for i64 {
     fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
         (*self).into_py_any(py).unwrap()
     }
 
-    fn to_pylist<T>(&self, _py: Python<'_>) -> Py<PyList> {
-        panic!("Can't convert usize to PyList")
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("i64", "PyList"))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("i64", "PyDict"))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("i64", "Buffer"))
+    }
+
+    fn to_usize(&self, _: Python<'_>) -> PyResult<usize> {
+        usize::try_from(*self)
+            .map_err(|_| PyValueError::new_err("Can't convert negative number to usize"))
     }
 
-    fn to_buffer(&self, _py: Python<'_>) -> Buffer {
-        panic!("Can't convert usize to Buffer")
+    fn to_num(&self, _: Python<'_>) -> PyResult<Num> {
+        Ok(Num::Int(*self))
     }
 
-    fn to_usize(&self, _: Python<'_>) -> usize {
-        *self
+    fn truthy(&self, _: Python<'_>) -> PyResult<bool> {
+        Ok(*self != 0)
     }
 }
+
+impl Var for f64 {
+    fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+        (*self).into_py_any(py).unwrap()
+    }
+
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("f64", "PyList"))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("f64", "PyDict"))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("f64", "Buffer"))
+    }
+
+    fn to_usize(&self, _: Python<'_>) -> PyResult<usize> {
+        Err(cant_convert("f64", "usize"))
+    }
+
+    fn to_num(&self, _: Python<'_>) -> PyResult<Num> {
+        Ok(Num::Float(*self))
+    }
+
+    fn truthy(&self, _: Python<'_>) -> PyResult<bool> {
+        Ok(*self != 0.0)
+    }
+}
+
+impl Var for bool {
+    fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+        (*self).into_py_any(py).unwrap()
+    }
+
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("bool", "PyList"))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("bool", "PyDict"))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("bool", "Buffer"))
+    }
+
+    fn to_usize(&self, _: Python<'_>) -> PyResult<usize> {
+        Ok(*self as usize)
+    }
+
+    fn to_num(&self, _: Python<'_>) -> PyResult<Num> {
+        Ok(Num::Int(*self as i64))
+    }
+
+    fn truthy(&self, _: Python<'_>) -> PyResult<bool> {
+        Ok(*self)
+    }
+}
+
+impl Var for String {
+    fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+        self.as_str().to_pyany(py)
+    }
+
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("str", "PyList"))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("str", "PyDict"))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("str", "Buffer"))
+    }
+
+    fn to_usize(&self, _py: Python<'_>) -> PyResult<usize> {
+        Err(cant_convert("str", "usize"))
+    }
+
+    fn to_num(&self, _py: Python<'_>) -> PyResult<Num> {
+        Err(cant_convert("str", "a number"))
+    }
+
+    fn truthy(&self, _py: Python<'_>) -> PyResult<bool> {
+        Ok(!self.is_empty())
+    }
+}
+
+impl Var for &str {
+    fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+        (*self).into_py_any(py).unwrap()
+    }
+
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("str", "PyList"))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("str", "PyDict"))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("str", "Buffer"))
+    }
+
+    fn to_usize(&self, _py: Python<'_>) -> PyResult<usize> {
+        Err(cant_convert("str", "usize"))
+    }
+
+    fn to_num(&self, _py: Python<'_>) -> PyResult<Num> {
+        Err(cant_convert("str", "a number"))
+    }
+
+    fn truthy(&self, _py: Python<'_>) -> PyResult<bool> {
+        Ok(!self.is_empty())
+    }
+}
+
+impl Var for Py<PyDict> {
+    fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+        self.clone_ref(py).into_any()
+    }
+
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("PyDict", "PyList"))
+    }
+
+    fn to_pydict(&self, py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Ok(self.clone_ref(py))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("PyDict", "Buffer"))
+    }
+
+    fn to_usize(&self, _py: Python<'_>) -> PyResult<usize> {
+        Err(cant_convert("PyDict", "usize"))
+    }
+
+    fn to_num(&self, _py: Python<'_>) -> PyResult<Num> {
+        Err(cant_convert("PyDict", "a number"))
+    }
+
+    fn truthy(&self, py: Python<'_>) -> PyResult<bool> {
+        Ok(!self.bind(py).is_empty())
+    }
+}
+
+/// A number that is either an int or a float, which is only known at runtime.
+/// Like in python, arithmetic on two ints gives an int, and arithmetic
+/// involving a float gives a float.
+#[derive(Debug, Clone, Copy)]
+pub enum Num {
+    Int(i64),
+    Float(f64),
+}
+
+impl Num {
+    fn as_f64(self) -> f64 {
+        match self {
+            Num::Int(i) => i as f64,
+            Num::Float(f) => f,
+        }
+    }
+
+    /// the number as an int, for things like `range` that don't take floats
+    pub fn to_int(self) -> PyResult<i64> {
+        match self {
+            Num::Int(i) => Ok(i),
+            Num::Float(_) => Err(PyTypeError::new_err(
+                "'float' object cannot be interpreted as an integer",
+            )),
+        }
+    }
+
+    /// the number as an index into a list or buffer, which has to be an int
+    pub fn to_index(self) -> PyResult<isize> {
+        match self {
+            Num::Int(i) => isize::try_from(i)
+                .map_err(|_| PyIndexError::new_err("cannot fit index into an index-sized integer")),
+            Num::Float(_) => Err(PyTypeError::new_err("indices must be integers")),
+        }
+    }
+}
+
+macro_rules! impl_num_op {
+    ($trait:ident, $fn:ident, $op:tt) => {
+        impl std::ops::$trait for Num {
+            type Output = Num;
+
+            fn $fn(self, rhs: Num) -> Num {
+                match (self, rhs) {
+                    (Num::Int(a), Num::Int(b)) => Num::Int(a $op b),
+                    (a, b) => Num::Float(a.as_f64() $op b.as_f64()),
+                }
+            }
+        }
+    };
+}
+
+impl_num_op!(Add, add, +);
+impl_num_op!(Sub, sub, -);
+impl_num_op!(Mul, mul, *);
+
+impl Num {
+    /// `self / rhs`. like in python, dividing two ints rounds down, so
+    /// `-7 / 2` is `-4`. dividing by zero is a ZeroDivisionError.
+    pub fn div(self, rhs: Num) -> PyResult<Num> {
+        match (self, rhs) {
+            (_, Num::Int(0)) => Err(PyZeroDivisionError::new_err("division by zero")),
+            (_, Num::Float(b)) if b == 0.0 => {
+                Err(PyZeroDivisionError::new_err("float division by zero"))
+            }
+            (Num::Int(a), Num::Int(b)) => {
+                let quotient = a
+                    .checked_div(b)
+                    .ok_or_else(|| PyOverflowError::new_err("integer division result too large"))?;
+                if a % b != 0 && (a < 0) != (b < 0) {
+                    Ok(Num::Int(quotient - 1))
+                } else {
+                    Ok(Num::Int(quotient))
+                }
+            }
+            (a, b) => Ok(Num::Float(a.as_f64() / b.as_f64())),
+        }
+    }
+
+    /// `self % rhs`. like in python, the result has the sign of `rhs`, so
+    /// `-7 % 2` is `1`. taking a modulo by zero is a ZeroDivisionError.
+    pub fn rem(self, rhs: Num) -> PyResult<Num> {
+        match (self, rhs) {
+            (_, Num::Int(0)) => Err(PyZeroDivisionError::new_err("modulo by zero")),
+            (_, Num::Float(b)) if b == 0.0 => Err(PyZeroDivisionError::new_err("float modulo")),
+            (Num::Int(a), Num::Int(b)) => {
+                let remainder = a.wrapping_rem(b);
+                if remainder != 0 && (remainder < 0) != (b < 0) {
+                    Ok(Num::Int(remainder + b))
+                } else {
+                    Ok(Num::Int(remainder))
+                }
+            }
+            (a, b) => {
+                let (a, b) = (a.as_f64(), b.as_f64());
+                let remainder = a % b;
+                if remainder != 0.0 && (remainder < 0.0) != (b < 0.0) {
+                    Ok(Num::Float(remainder + b))
+                } else {
+                    Ok(Num::Float(remainder))
+                }
+            }
+        }
+    }
+}
+
+impl std::ops::Neg for Num {
+    type Output = Num;
+
+    fn neg(self) -> Num {
+        match self {
+            Num::Int(i) => Num::Int(-i),
+            Num::Float(f) => Num::Float(-f),
+        }
+    }
+}
+
+impl PartialEq for Num {
+    fn eq(&self, other: &Num) -> bool {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => a == b,
+            (a, b) => a.as_f64() == b.as_f64(),
+        }
+    }
+}
+
+impl PartialOrd for Num {
+    fn partial_cmp(&self, other: &Num) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => a.partial_cmp(b),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+}
+
+impl Var for Num {
+    fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+        match self {
+            Num::Int(i) => i.to_pyany(py),
+            Num::Float(f) => f.to_pyany(py),
+        }
+    }
+
+    fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+        Err(cant_convert("number", "PyList"))
+    }
+
+    fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+        Err(cant_convert("number", "PyDict"))
+    }
+
+    fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+        Err(cant_convert("number", "Buffer"))
+    }
+
+    fn to_usize(&self, py: Python<'_>) -> PyResult<usize> {
+        match self {
+            Num::Int(i) => i.to_usize(py),
+            Num::Float(_) => Err(cant_convert("float", "usize")),
+        }
+    }
+
+    fn to_num(&self, _: Python<'_>) -> PyResult<Num> {
+        Ok(*self)
+    }
+
+    fn truthy(&self, py: Python<'_>) -> PyResult<bool> {
+        match self {
+            Num::Int(i) => i.truthy(py),
+            Num::Float(f) => f.truthy(py),
+        }
+    }
+}
+
+/// implement Var for a class from the source code. objects are a `Py<Class>`
+/// in the generated code.
+macro_rules! impl_var_for_class {
+    ($class:ty) => {
+        impl Var for Py<$class> {
+            fn to_pyany(&self, py: Python<'_>) -> Py<PyAny> {
+                self.clone_ref(py).into_any()
+            }
+
+            fn to_pylist<T>(&self, _py: Python<'_>) -> PyResult<Py<PyList>> {
+                Err(cant_convert("object", "PyList"))
+            }
+
+            fn to_pydict(&self, _py: Python<'_>) -> PyResult<Py<PyDict>> {
+                Err(cant_convert("object", "PyDict"))
+            }
+
+            fn to_buffer(&self, _py: Python<'_>) -> PyResult<Buffer> {
+                Err(cant_convert("object", "Buffer"))
+            }
+
+            fn to_usize(&self, _py: Python<'_>) -> PyResult<usize> {
+                Err(cant_convert("object", "usize"))
+            }
+
+            fn to_num(&self, _py: Python<'_>) -> PyResult<Num> {
+                Err(cant_convert("object", "number"))
+            }
+
+            fn truthy(&self, py: Python<'_>) -> PyResult<bool> {
+                self.to_pyany(py).truthy(py)
+            }
+        }
+    };
+}
@@ -1,28 +1,410 @@
 use std::collections::{HashMap, HashSet};
 
 use crate::grammar_ast::*;
+use crate::span::Span;
 
-pub fn clean_ast(region: &mut Region) {
+pub fn clean_ast(region: &mut Region, file_path: &str) {
     move_toplevel_statements_to_function(region);
+    check_classes(region, file_path);
+    resolve_globals(region, file_path);
 
     for item in &mut region.body {
         match item {
-            RegionItem::Function(function) => {
-                // do 3 passes
-                for _ in 0..3 {
-                    clean_function(function);
+            RegionItem::Function(function) => clean_function(function, &[], file_path),
+            RegionItem::Class(class) => {
+                for method in &mut class.methods {
+                    clean_function(method, &[], file_path);
                 }
             }
 
             // all toplevel statements were put in a function by
             // move_toplevel_statements_to_function()
-            RegionItem::Statement(s) => unreachable!(),
+            RegionItem::Statement(_) => unreachable!(),
+
+            // the values of globals are cleaned by resolve_globals()
+            RegionItem::Global(_) => {}
+        }
+    }
+}
+
+/// the functions in each region, from every file that was loaded. the file
+/// each region is in is only used for error messages.
+pub fn region_functions<'a>(
+    regions: impl IntoIterator<Item = (&'a str, &'a Region)>,
+) -> HashMap<String, Vec<String>> {
+    let mut functions = HashMap::new();
+    let mut defined_in = HashMap::new();
+    for (file_path, region) in regions {
+        let mut names: Vec<String> = vec![];
+        let mut duplicated = false;
+        for item in &region.body {
+            let RegionItem::Function(function) = item else {
+                continue;
+            };
+
+            if names.contains(&function.name) {
+                eprintln!(
+                    "Error in file {file_path}:{}: `{}` is already defined in this region",
+                    function.span, function.name
+                );
+                duplicated = true;
+            }
+            names.push(function.name.clone());
         }
+        if duplicated {
+            std::process::exit(1);
+        }
+
+        if let Some(other_file) = defined_in.insert(region.name.clone(), file_path) {
+            if other_file == file_path {
+                eprintln!(
+                    "Error in file {file_path}:{}: region `{}` is defined more than once",
+                    region.span, region.name
+                );
+            } else {
+                eprintln!(
+                    "Error in file {file_path}:{}: region `{}` is already defined in {other_file}",
+                    region.span, region.name
+                );
+            }
+            std::process::exit(1);
+        }
+        functions.insert(region.name.clone(), names);
     }
+
+    functions
 }
 
+/// turn method calls on a region, e.g. `Other.f(x)`, into calls to a function
+/// in that region. a variable with the same name as a region hides it.
+/// functions brought in with `use Other.f;` can be called as just `f(x)`.
+pub fn resolve_region_calls(
+    region: &mut Region,
+    functions: &HashMap<String, Vec<String>>,
+    file_path: &str,
+) {
+    let mut errors = vec![];
+
+    // the region each used function comes from, by name
+    let own_functions = &functions[&region.name];
+    let mut used = HashMap::new();
+    for u in &region.uses {
+        let Some(region_functions) = functions.get(&u.region) else {
+            errors.push((u.span, format!("there is no region `{}`", u.region)));
+            continue;
+        };
+        if !region_functions.contains(&u.function) {
+            errors.push((
+                u.span,
+                format!("region `{}` has no function `{}`", u.region, u.function),
+            ));
+        } else if own_functions.contains(&u.function) {
+            errors.push((
+                u.span,
+                format!("`{}` is already defined in this region", u.function),
+            ));
+        } else if let Some(other) = used.insert(u.function.clone(), u.region.clone()) {
+            errors.push((
+                u.span,
+                format!("`{}` is already used from region `{other}`", u.function),
+            ));
+        }
+    }
+
+    // python imports hide regions with the same name
+    let imports = region
+        .python_imports
+        .iter()
+        .map(|import| import.name().to_string())
+        .collect::<Vec<_>>();
+    for import in &region.python_imports {
+        if used.contains_key(import.name()) {
+            errors.push((
+                import.span,
+                format!(
+                    "`{}` is already used from region `{}`",
+                    import.name(),
+                    used[import.name()]
+                ),
+            ));
+        }
+    }
+
+    let toplevel_variables = region
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            RegionItem::Statement(statement) => Some(statement),
+            RegionItem::Function(_) | RegionItem::Class(_) | RegionItem::Global(_) => None,
+        })
+        .flat_map(variables_declared)
+        .map(String::from)
+        .chain(imports.iter().cloned())
+        .collect::<Vec<_>>();
+
+    let bodies = region.body.iter_mut().flat_map(|item| match item {
+        RegionItem::Function(function) => {
+            let mut variables = function_variables(function);
+            variables.extend(imports.iter().cloned());
+            vec![(function.body.iter_mut().collect::<Vec<_>>(), variables)]
+        }
+        RegionItem::Class(class) => class
+            .methods
+            .iter_mut()
+            .map(|method| {
+                let mut variables = function_variables(method);
+                variables.extend(imports.iter().cloned());
+                (method.body.iter_mut().collect(), variables)
+            })
+            .collect(),
+        RegionItem::Statement(statement) => vec![(vec![statement], toplevel_variables.clone())],
+        RegionItem::Global(_) => vec![],
+    });
+    for (statements, variables) in bodies {
+        for statement in statements {
+            errors.extend(region_call_errors(
+                exprs_in_statment(statement),
+                functions,
+                &variables,
+            ));
+            resolve_calls(statement, &region.name, functions, &used, &variables);
+        }
+    }
+
+    // the values of globals can call functions too
+    for item in &mut region.body {
+        if let RegionItem::Global(global) = item {
+            errors.extend(region_call_errors(
+                expr_and_nested_exprs(&global.value),
+                functions,
+                &imports,
+            ));
+            run_on_expr_and_nested(&mut global.value, |expr| {
+                resolve_call(expr, &region.name, functions, &used, &imports)
+            });
+        }
+    }
+
+    if !errors.is_empty() {
+        for (span, error) in errors {
+            eprintln!("Error in file {file_path}:{span}: {error}");
+        }
+        std::process::exit(1);
+    }
+}
+
+/// the parameters and variables declared in a function
+fn function_variables(function: &Function) -> Vec<String> {
+    let mut variables = function
+        .params
+        .iter()
+        .map(|param| param.name.clone())
+        .collect::<Vec<_>>();
+    variables.extend(
+        function
+            .body
+            .iter()
+            .flat_map(variables_declared)
+            .map(String::from),
+    );
+    variables
+}
+
+/// the variables a function or lambda uses that aren't its own, like the
+/// ones a lambda captures or the region's globals. a call could be to a
+/// variable that holds a function, so the functions it calls are included.
+fn free_variables(function: &Function) -> Vec<&str> {
+    let declared = own_variables(function);
+    let mut free = vec![];
+
+    let assigned = function.body.iter().flat_map(variables_modified);
+    let used = function
+        .body
+        .iter()
+        .flat_map(exprs_in_statment)
+        .flat_map(|expr| match &expr.kind {
+            ExprKind::Variable(name) | ExprKind::Call(name, _) => vec![name.as_str()],
+            ExprKind::Lambda(lambda) => free_variables(&lambda.function),
+            _ => vec![],
+        });
+    for var in assigned.chain(used) {
+        if !declared.iter().any(|d| d == var) && !free.contains(&var) {
+            free.push(var);
+        }
+    }
+
+    free
+}
+
+/// find the calls to functions that other regions don't have, including in
+/// lambdas
+fn region_call_errors(
+    exprs: Vec<&Expr>,
+    functions: &HashMap<String, Vec<String>>,
+    variables: &[String],
+) -> Vec<(Span, String)> {
+    let mut errors = vec![];
+
+    for expr in exprs {
+        if let ExprKind::Lambda(lambda) = &expr.kind {
+            // a lambda can use the variables of the function it's in too
+            let mut lambda_variables = variables.to_vec();
+            lambda_variables.extend(function_variables(&lambda.function));
+            let lambda_exprs = lambda.function.body.iter().flat_map(exprs_in_statment);
+            errors.extend(region_call_errors(
+                lambda_exprs.collect(),
+                functions,
+                &lambda_variables,
+            ));
+        }
+
+        let Some((region, function)) = region_call(expr, functions, variables) else {
+            continue;
+        };
+        if !functions[region].iter().any(|f| f == function) {
+            errors.push((
+                expr.span,
+                format!("region `{region}` has no function `{function}`"),
+            ));
+        }
+    }
+
+    errors
+}
+
+/// replace `Region.function(...)` and calls to used functions with region
+/// calls, and functions that are used as values with function values
+fn resolve_calls(
+    statement: &mut Statement,
+    region_name: &str,
+    functions: &HashMap<String, Vec<String>>,
+    used: &HashMap<String, String>,
+    variables: &[String],
+) {
+    run_on_all_exprs(statement, |expr| {
+        resolve_call(expr, region_name, functions, used, variables)
+    });
+}
+
+/// resolve_calls() for a single expression, without the ones nested in it
+fn resolve_call(
+    expr: &mut Expr,
+    region_name: &str,
+    functions: &HashMap<String, Vec<String>>,
+    used: &HashMap<String, String>,
+    variables: &[String],
+) {
+    let region_function = region_call(expr, functions, variables)
+        .map(|(region, function)| (region.to_string(), function.to_string()));
+    match &mut expr.kind {
+        ExprKind::MethodCall(_, _, args) if region_function.is_some() => {
+            let (region, function) = region_function.unwrap();
+            expr.kind = ExprKind::RegionCall(region, function, args.clone());
+        }
+        ExprKind::Field(_, _) if region_function.is_some() => {
+            let (region, function) = region_function.unwrap();
+            expr.kind = ExprKind::FunctionValue(region, function);
+        }
+        ExprKind::Call(name, args) if used.contains_key(name) => {
+            expr.kind = ExprKind::RegionCall(used[name].clone(), name.clone(), args.clone());
+        }
+        ExprKind::Variable(name) if !variables.contains(name) => {
+            if functions[region_name].contains(name) {
+                expr.kind = ExprKind::FunctionValue(region_name.to_string(), name.clone());
+            } else if let Some(region) = used.get(name) {
+                expr.kind = ExprKind::FunctionValue(region.clone(), name.clone());
+            }
+        }
+        ExprKind::Lambda(lambda) => {
+            let mut lambda_variables = variables.to_vec();
+            lambda_variables.extend(function_variables(&lambda.function));
+            for statement in &mut lambda.function.body {
+                resolve_calls(statement, region_name, functions, used, &lambda_variables);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// the region and function of `Region.function(...)`, or of
+/// `Region.function` used as a value
+fn region_call<'a>(
+    expr: &'a Expr,
+    functions: &HashMap<String, Vec<String>>,
+    variables: &[String],
+) -> Option<(&'a str, &'a str)> {
+    let (ExprKind::MethodCall(obj, function, _) | ExprKind::Field(obj, function)) = &expr.kind
+    else {
+        return None;
+    };
+    let ExprKind::Variable(region) = &obj.kind else {
+        return None;
+    };
+
+    if !functions.contains_key(region) || variables.contains(region) {
+        return None;
+    }
+
+    Some((region, function))
+}
+
+/// check that every method takes `self` first, and that each class has a
+/// name of its own
+fn check_classes(region: &Region, file_path: &str) {
+    let mut names = region
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            RegionItem::Function(function) => Some(function.name.as_str()),
+            RegionItem::Statement(_) | RegionItem::Class(_) | RegionItem::Global(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut errors = vec![];
+    for item in &region.body {
+        let RegionItem::Class(class) = item else {
+            continue;
+        };
+
+        if names.contains(&class.name.as_str()) {
+            errors.push((
+                class.span,
+                format!("`{}` is already defined in this region", class.name),
+            ));
+        }
+        names.push(&class.name);
+
+        for method in &class.methods {
+            match method.params.first() {
+                Some(Variable {
+                    name,
+                    var_type: None,
+                }) if name == "self" => {}
+                _ => errors.push((
+                    method.span,
+                    format!(
+                        "the first parameter of method `{}` must be `self`",
+                        method.name
+                    ),
+                )),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        for (span, error) in errors {
+            eprintln!("Error in file {file_path}:{span}: {error}");
+        }
+        std::process::exit(1);
+    }
+}
+
+/// put the region's top-level statements in a `main` function. a top-level
+/// `let` that the functions use is a global instead, so it's set when the
+/// module is imported rather than when `main` runs.
 fn move_toplevel_statements_to_function(region: &mut Region) {
     let mut toplevel_statements = vec![];
+    let shared = shared_variables(region);
 
     // take toplevel statements out of region body and put them in vec above
     region.body = region
@@ -31,6 +413,18 @@ fn move_toplevel_statements_to_function(region: &mut Region) {
         .into_iter()
         .filter_map(|item| match item {
             RegionItem::Function(function) => Some(RegionItem::Function(function)),
+            RegionItem::Class(class) => Some(RegionItem::Class(class)),
+            RegionItem::Global(global) => Some(RegionItem::Global(global)),
+            RegionItem::Statement(Statement {
+                kind: StatementKind::Let(name, var_type, value),
+                span,
+            }) if shared.contains(&name) => Some(RegionItem::Global(Global {
+                name,
+                var_type,
+                value,
+                constant: false,
+                span,
+            })),
             RegionItem::Statement(s) => {
                 toplevel_statements.push(s);
                 None
@@ -44,30 +438,411 @@ fn move_toplevel_statements_to_function(region: &mut Region) {
 
     // create a main function for those toplevel statements
     let toplevel = Function {
-        name: "<Identifier, main>".to_string(),
+        name: "main".to_string(),
         params: vec![],
+        return_type: None,
         body: toplevel_statements.clone(),
+        span: region.span,
+        doc: None,
     };
     region.body.push(RegionItem::Function(toplevel));
 }
 
-fn clean_function(function: &mut Function) {
+/// the top-level `let`s that the region's functions and methods use, and the
+/// ones that the values of those use
+fn shared_variables(region: &Region) -> Vec<String> {
+    let lets = region
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            RegionItem::Statement(Statement {
+                kind: StatementKind::Let(name, _, value),
+                ..
+            }) => Some((name, value)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut shared = region
+        .body
+        .iter()
+        .flat_map(|item| match item {
+            RegionItem::Function(function) => free_variables(function),
+            RegionItem::Class(class) => class.methods.iter().flat_map(free_variables).collect(),
+            RegionItem::Statement(_) | RegionItem::Global(_) => vec![],
+        })
+        .filter(|var| lets.iter().any(|(name, _)| name == var))
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    // a value can only use the lets before it, so going backwards finds
+    // everything they need
+    for (i, (name, value)) in lets.iter().enumerate().rev() {
+        if !shared.contains(name) {
+            continue;
+        }
+        for var in variables_used(value) {
+            if lets[..i].iter().any(|(name, _)| *name == var) && !shared.iter().any(|s| s == var) {
+                shared.push(var.to_string());
+            }
+        }
+    }
+
+    shared
+}
+
+/// inline the region's constants, and make every use of its other globals
+/// read or change the module's attribute. variables with the same name hide
+/// them. the value of a global can only use the globals before it.
+fn resolve_globals(region: &mut Region, file_path: &str) {
+    let mut errors = vec![];
+    let mut constants = HashMap::new();
+    let mut globals = vec![];
+
+    let functions = region
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            RegionItem::Function(function) => Some(function.name.as_str()),
+            RegionItem::Statement(_) | RegionItem::Class(_) | RegionItem::Global(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let mut imports = vec![];
+    for import in &region.python_imports {
+        let name = import.name().to_string();
+        if imports.contains(&name) || functions.contains(&name.as_str()) {
+            errors.push((
+                import.span,
+                format!("`{name}` is already defined in this region"),
+            ));
+        }
+        imports.push(name);
+    }
+
+    for item in &mut region.body {
+        let RegionItem::Global(global) = item else {
+            continue;
+        };
+
+        let mut value = global.value.clone();
+        run_on_expr_and_nested(&mut value, |expr| {
+            resolve_global_use(expr, &constants, &globals, &imports, &[], file_path)
+        });
+        simplify_expression(&mut value, &HashMap::new());
+
+        let undeclared = expr_and_nested_exprs(&value)
+            .into_iter()
+            .filter_map(|expr| match &expr.kind {
+                ExprKind::Variable(var) => {
+                    Some((expr.span, format!("undeclared variable `{var}` used")))
+                }
+                _ => None,
+            });
+        errors.extend(undeclared);
+        errors.extend(
+            bad_arguments(expr_and_nested_exprs(&value))
+                .into_iter()
+                .map(|(message, span)| (span, message)),
+        );
+
+        run_on_expr_and_nested(&mut value, |expr| {
+            if let ExprKind::Lambda(lambda) = &mut expr.kind {
+                clean_function(&mut lambda.function, &[], file_path);
+            }
+        });
+
+        if constants.contains_key(&global.name)
+            || globals.contains(&global.name)
+            || imports.contains(&global.name)
+        {
+            errors.push((
+                global.span,
+                format!("`{}` is already defined in this region", global.name),
+            ));
+        }
+        if !global.constant {
+            globals.push(global.name.clone());
+        } else if matches!(
+            value.kind,
+            ExprKind::Number(_)
+                | ExprKind::Float(_)
+                | ExprKind::Bool(_)
+                | ExprKind::None
+                | ExprKind::StringLiteral(_)
+        ) {
+            constants.insert(global.name.clone(), (*value).clone());
+        } else {
+            errors.push((
+                value.span,
+                format!(
+                    "constant `{}` has to be a number, string, bool or none",
+                    global.name
+                ),
+            ));
+        }
+        global.value = value;
+    }
+
+    if !errors.is_empty() {
+        for (span, error) in errors {
+            eprintln!("Error in file {file_path}:{span}: {error}");
+        }
+        std::process::exit(1);
+    }
+
+    for item in &mut region.body {
+        let functions = match item {
+            RegionItem::Function(function) => vec![function],
+            RegionItem::Class(class) => class.methods.iter_mut().collect(),
+            RegionItem::Statement(_) | RegionItem::Global(_) => vec![],
+        };
+        for function in functions {
+            let locals = own_variables(function);
+            for statement in &mut function.body {
+                resolve_global_uses(
+                    statement, &constants, &globals, &imports, &locals, file_path,
+                );
+            }
+        }
+    }
+}
+
+/// the parameters of a function and the variables it declares with `let`,
+/// which hide the region's globals
+fn own_variables(function: &Function) -> Vec<String> {
+    function
+        .params
+        .iter()
+        .map(|param| param.name.as_str())
+        .chain(function.body.iter().flat_map(|s| declarations(s, false)))
+        .map(String::from)
+        .collect()
+}
+
+/// resolve_globals() for a statement, including the lambdas in it
+fn resolve_global_uses(
+    statement: &mut Statement,
+    constants: &HashMap<String, Expr>,
+    globals: &[String],
+    imports: &[String],
+    locals: &[String],
+    file_path: &str,
+) {
+    let mut errors = vec![];
+    run_on_all_statements(statement, &mut |statement| {
+        let StatementKind::Assignment(name, value) = &statement.kind else {
+            return;
+        };
+        if locals.contains(name) {
+            return;
+        }
+
+        if constants.contains_key(name) {
+            errors.push((statement.span, name.clone()));
+        } else if globals.contains(name) {
+            statement.kind = StatementKind::GlobalAssignment(name.clone(), value.clone());
+        }
+    });
+    if !errors.is_empty() {
+        for (span, name) in errors {
+            eprintln!("Error in file {file_path}:{span}: can't assign to constant `{name}`");
+        }
+        std::process::exit(1);
+    }
+
+    run_on_all_exprs(statement, |expr| {
+        resolve_global_use(expr, constants, globals, imports, locals, file_path)
+    });
+}
+
+/// resolve_global_uses() for a single expression, without the ones nested in
+/// it
+fn resolve_global_use(
+    expr: &mut Expr,
+    constants: &HashMap<String, Expr>,
+    globals: &[String],
+    imports: &[String],
+    locals: &[String],
+    file_path: &str,
+) {
+    match &mut expr.kind {
+        ExprKind::Variable(name) if !locals.contains(name) => {
+            if let Some(value) = constants.get(name) {
+                expr.kind = value.kind.clone();
+            } else if globals.contains(name) {
+                expr.kind = ExprKind::Global(name.clone());
+            } else if imports.contains(name) {
+                expr.kind = ExprKind::Import(name.clone());
+            }
+        }
+        // calling a global calls the function it holds
+        ExprKind::Call(name, args) if !locals.contains(name) && globals.contains(name) => {
+            let callee = Expr::new(ExprKind::Global(name.clone()), expr.span);
+            expr.kind = ExprKind::CallValue(Box::new(callee), args.clone());
+        }
+        ExprKind::Call(name, args) if !locals.contains(name) && imports.contains(name) => {
+            let callee = Expr::new(ExprKind::Import(name.clone()), expr.span);
+            expr.kind = ExprKind::CallValue(Box::new(callee), args.clone());
+        }
+        ExprKind::Lambda(lambda) => {
+            let mut lambda_locals = locals.to_vec();
+            lambda_locals.extend(own_variables(&lambda.function));
+            for statement in &mut lambda.function.body {
+                resolve_global_uses(
+                    statement,
+                    constants,
+                    globals,
+                    imports,
+                    &lambda_locals,
+                    file_path,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// clean a function, or a lambda that captures some variables from the
+/// function it's in. the lambdas in it are cleaned once it's done, so a
+/// nested lambda isn't cleaned again for every pass over the ones around it.
+fn clean_function(function: &mut Function, captures: &[String], file_path: &str) {
+    // do 3 passes
+    for _ in 0..3 {
+        clean_function_pass(function, captures, file_path);
+    }
+
+    for statement in &mut function.body {
+        run_on_all_exprs(statement, |expr| {
+            if let ExprKind::Lambda(lambda) = &mut expr.kind {
+                clean_function(&mut lambda.function, &lambda.captures, file_path);
+            }
+        });
+    }
+}
+
+fn clean_function_pass(function: &mut Function, captures: &[String], file_path: &str) {
     // add "return none" to end of function (will be removed later if not needed)
-    let return_none = Statement::Return(Box::new(Expr::Uninitialized));
-    function.body.push(return_none);
+    let return_none = StatementKind::Return(Box::new(Expr::new(
+        ExprKind::Uninitialized,
+        Span::default(),
+    )));
+    function
+        .body
+        .push(Statement::new(return_none, Span::default()));
+
+    // break and continue only make sense inside a loop
+    let jumps = loop_jumps_outside_loop(&function.body);
+    if !jumps.is_empty() {
+        for (jump, span) in jumps {
+            eprintln!("Error in file {file_path}:{span}: `{jump}` outside of a loop");
+        }
+        std::process::exit(1);
+    }
+
+    let catches = bad_catches(&function.body);
+    if !catches.is_empty() {
+        for (message, span) in catches {
+            eprintln!("Error in file {file_path}:{span}: {message}");
+        }
+        std::process::exit(1);
+    }
 
     // eliminate unreachable code
     unreachable_code_elimination(&mut function.body);
 
     // simplify all statements in function
+    let numbers = number_variables(function);
     for statement in &mut function.body {
-        simplify_statement(statement);
+        simplify_statement(statement, &numbers);
+    }
+
+    // only keep the branch that runs when the condition is a constant
+    remove_constant_branches(&mut function.body);
+
+    // dividing by a constant zero would always raise an exception. this runs
+    // after constant folding, so it also finds divisors like `1 - 1`.
+    let zero_divisions = function
+        .body
+        .iter()
+        .flat_map(exprs_in_statment)
+        .filter_map(|expr| match &expr.kind {
+            ExprKind::Binary(_, BinaryOp::Div, rhs) if is_constant_zero(rhs) => {
+                Some(("division", expr.span))
+            }
+            ExprKind::Binary(_, BinaryOp::Mod, rhs) if is_constant_zero(rhs) => {
+                Some(("modulo", expr.span))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !zero_divisions.is_empty() {
+        for (op, span) in zero_divisions {
+            eprintln!("Error in file {file_path}:{span}: {op} by zero");
+        }
+        std::process::exit(1);
+    }
+
+    // range takes a stop, a start and stop, or a start, stop and step
+    let bad_ranges = function
+        .body
+        .iter()
+        .flat_map(exprs_in_statment)
+        .filter_map(|expr| match &expr.kind {
+            ExprKind::Call(name, args) if name == "range" && !(1..=3).contains(&args.len()) => {
+                Some(("`range` takes 1 to 3 arguments", expr.span))
+            }
+            ExprKind::Call(name, args)
+                if name == "range"
+                    && args.get(2).map(|step| &step.kind) == Some(&ExprKind::Number(0)) =>
+            {
+                Some(("`range` step can't be zero", expr.span))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if !bad_ranges.is_empty() {
+        for (message, span) in bad_ranges {
+            eprintln!("Error in file {file_path}:{span}: {message}");
+        }
+        std::process::exit(1);
+    }
+
+    // calling a variable calls the function it holds. lambdas are cleaned
+    // like functions, with the variables they capture declared in them.
+    let mut variables = function_variables(function);
+    variables.extend(captures.iter().cloned());
+    for statement in &mut function.body {
+        run_on_all_exprs(statement, |expr| {
+            if let ExprKind::Call(name, args) = &expr.kind {
+                if variables.contains(name) {
+                    let callee = Expr::new(ExprKind::Variable(name.clone()), expr.span);
+                    expr.kind = ExprKind::CallValue(Box::new(callee), args.clone());
+                }
+            }
+
+            if let ExprKind::Lambda(lambda) = &mut expr.kind {
+                lambda.captures = free_variables(&lambda.function)
+                    .into_iter()
+                    .filter(|var| variables.iter().any(|v| v == var))
+                    .map(String::from)
+                    .collect();
+            }
+        });
+    }
+
+    let arguments = bad_arguments(function.body.iter().flat_map(exprs_in_statment).collect());
+    if !arguments.is_empty() {
+        for (message, span) in arguments {
+            eprintln!("Error in file {file_path}:{span}: {message}");
+        }
+        std::process::exit(1);
     }
 
     let mut declared = function
         .params
         .iter()
         .map(|param| param.name.as_str())
+        .chain(captures.iter().map(String::as_str))
         .collect::<Vec<_>>();
     let mut used = vec![];
 
@@ -80,15 +855,19 @@ fn clean_function(function: &mut Function) {
         }
     }
 
-    // get used but undeclared variables
-    let undeclared = used
+    // get used but undeclared variables, and where they are used
+    let undeclared = function
+        .body
         .iter()
-        .filter(|var| !declared.contains(var))
-        .map(|var| var.to_string())
+        .flat_map(exprs_in_statment)
+        .filter_map(|expr| match &expr.kind {
+            ExprKind::Variable(var) if !declared.contains(&var.as_str()) => Some((var, expr.span)),
+            _ => None,
+        })
         .collect::<Vec<_>>();
     if !undeclared.is_empty() {
-        for var in undeclared {
-            eprintln!("Error: undeclared variable `{}` used", var);
+        for (var, span) in undeclared {
+            eprintln!("Error in file {file_path}:{span}: undeclared variable `{var}` used");
         }
         std::process::exit(1);
     }
@@ -100,16 +879,22 @@ fn clean_function(function: &mut Function) {
         .map(|var| var.to_string())
         .collect::<Vec<_>>();
 
+    // temp variables can't have the name of one from the source code
+    let taken_names = declared
+        .iter()
+        .map(|var| var.to_string())
+        .collect::<Vec<_>>();
+
     // replace unused variables declarations and assignments with just the rhs
     for statement in &mut function.body {
-        match statement {
-            Statement::Let(name, expr) | Statement::Assignment(name, expr) => {
+        match &mut statement.kind {
+            StatementKind::Let(name, _, expr) | StatementKind::Assignment(name, expr) => {
                 if !unused.contains(name) {
                     continue;
                 }
 
                 // remove assignment and keep rhs
-                *statement = Statement::Expression(expr.clone());
+                statement.kind = StatementKind::Expression(expr.clone());
             }
 
             _ => {}
@@ -120,56 +905,91 @@ fn clean_function(function: &mut Function) {
     eliminate_common_subexpressions(&mut function.body, SubexprGraph::default());
 
     // move loop invariant expressions outside of loop
-    loop_invariant_motion(&mut function.body);
-
+    loop_invariant_motion(&mut function.body, &numbers, &taken_names);
 }
 
-fn expr_and_nested_exprs(expr: &Box<Expr>) -> Vec<&Box<Expr>> {
+fn expr_and_nested_exprs(expr: &Expr) -> Vec<&Expr> {
     let mut exprs = vec![expr];
-    match expr.as_ref() {
-        Expr::Uninitialized => {}
-        Expr::Number(_) => {}
-        Expr::StringLiteral(_) => {}
-        Expr::Variable(_) => {}
-        Expr::Call(_, args) => {
+    match &expr.kind {
+        ExprKind::Uninitialized => {}
+        ExprKind::Number(_) => {}
+        ExprKind::Float(_) => {}
+        ExprKind::Bool(_) | ExprKind::None => {}
+        ExprKind::StringLiteral(_) => {}
+        ExprKind::Variable(_) => {}
+        ExprKind::Call(_, args) | ExprKind::RegionCall(_, _, args) => {
             for arg in args {
                 exprs.extend(expr_and_nested_exprs(arg));
             }
         }
-        Expr::Array(items) => {
+        ExprKind::Array(items) => {
             for item in items {
                 exprs.extend(expr_and_nested_exprs(item));
             }
         }
-        Expr::Binary(lhs, _, rhs) => {
+        ExprKind::Dict(entries) => {
+            for (key, value) in entries {
+                exprs.extend(expr_and_nested_exprs(key));
+                exprs.extend(expr_and_nested_exprs(value));
+            }
+        }
+        ExprKind::Binary(lhs, _, rhs) => {
             exprs.extend(expr_and_nested_exprs(lhs));
             exprs.extend(expr_and_nested_exprs(rhs));
         }
-        Expr::MethodCall(expr, _, args) => {
+        ExprKind::Unary(_, expr) => {
+            exprs.extend(expr_and_nested_exprs(expr));
+        }
+        ExprKind::Index(value, index) => {
+            exprs.extend(expr_and_nested_exprs(value));
+            exprs.extend(expr_and_nested_exprs(index));
+        }
+        ExprKind::Field(value, _) => {
+            exprs.extend(expr_and_nested_exprs(value));
+        }
+        ExprKind::MethodCall(expr, _, args) | ExprKind::CallValue(expr, args) => {
             exprs.extend(expr_and_nested_exprs(expr));
             for arg in args {
                 exprs.extend(expr_and_nested_exprs(arg));
             }
         }
+        ExprKind::KeywordArgument(_, value) => {
+            exprs.extend(expr_and_nested_exprs(value));
+        }
+        // a lambda's body is cleaned on its own, like a function's
+        ExprKind::Lambda(_)
+        | ExprKind::FunctionValue(_, _)
+        | ExprKind::Global(_)
+        | ExprKind::Import(_) => {}
     }
     exprs
 }
 
-fn exprs_in_statment(statement: &Statement) -> Vec<&Box<Expr>> {
-    match statement {
-        Statement::Noop => vec![],
-        Statement::Let(_, expr) => expr_and_nested_exprs(expr),
-        Statement::Return(expr) => expr_and_nested_exprs(expr),
-        Statement::Expression(expr) => expr_and_nested_exprs(expr),
-        Statement::Call(_, args) => args.iter().flat_map(expr_and_nested_exprs).collect(),
-        Statement::If(expr, statements) => {
+/// check if an expression is a zero constant: `0`, `0.0`, `-0.0` or `false`
+fn is_constant_zero(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Number(n) => *n == 0,
+        ExprKind::Float(f) => f.0 == 0.0,
+        ExprKind::Bool(b) => !b,
+        _ => false,
+    }
+}
+
+fn exprs_in_statment(statement: &Statement) -> Vec<&Expr> {
+    match &statement.kind {
+        StatementKind::Noop => vec![],
+        StatementKind::Let(_, _, expr) => expr_and_nested_exprs(expr),
+        StatementKind::Return(expr) => expr_and_nested_exprs(expr),
+        StatementKind::Expression(expr) => expr_and_nested_exprs(expr),
+        StatementKind::Call(_, args) => args.iter().flat_map(expr_and_nested_exprs).collect(),
+        StatementKind::If(expr, statements) => {
             let mut exprs = expr_and_nested_exprs(expr);
             for statement in statements {
                 exprs.extend(exprs_in_statment(statement));
             }
             exprs
         }
-        Statement::IfElse(expr, if_statements, else_statements) => {
+        StatementKind::IfElse(expr, if_statements, else_statements) => {
             let mut exprs = expr_and_nested_exprs(expr);
             for statement in if_statements {
                 exprs.extend(exprs_in_statment(statement));
@@ -179,8 +999,8 @@ fn exprs_in_statment(statement: &Statement) -> Vec<&Box<Expr>> {
             }
             exprs
         }
-        Statement::ForLoop(init, cond, update, statements) => {
-            let mut exprs = vec![cond];
+        StatementKind::ForLoop(init, cond, update, statements) => {
+            let mut exprs = expr_and_nested_exprs(cond);
             for statement in [init, update] {
                 exprs.extend(exprs_in_statment(statement));
             }
@@ -189,48 +1009,112 @@ fn exprs_in_statment(statement: &Statement) -> Vec<&Box<Expr>> {
             }
             exprs
         }
-        Statement::Assignment(_, expr) => expr_and_nested_exprs(expr),
+        StatementKind::ForIn(_, iter, statements) | StatementKind::While(iter, statements) => {
+            let mut exprs = expr_and_nested_exprs(iter);
+            for statement in statements {
+                exprs.extend(exprs_in_statment(statement));
+            }
+            exprs
+        }
+        StatementKind::Break | StatementKind::Continue => vec![],
+        StatementKind::Assignment(_, expr) | StatementKind::GlobalAssignment(_, expr) => {
+            expr_and_nested_exprs(expr)
+        }
+        StatementKind::IndexAssignment(value, index, expr) => {
+            let mut exprs = expr_and_nested_exprs(value);
+            exprs.extend(expr_and_nested_exprs(index));
+            exprs.extend(expr_and_nested_exprs(expr));
+            exprs
+        }
+        StatementKind::FieldAssignment(value, _, expr) => {
+            let mut exprs = expr_and_nested_exprs(value);
+            exprs.extend(expr_and_nested_exprs(expr));
+            exprs
+        }
+        StatementKind::Try(statements, catches) => {
+            let mut exprs = vec![];
+            for statement in statements {
+                exprs.extend(exprs_in_statment(statement));
+            }
+            for catch in catches {
+                for statement in &catch.body {
+                    exprs.extend(exprs_in_statment(statement));
+                }
+            }
+            exprs
+        }
+        StatementKind::Raise(expr) => expr_and_nested_exprs(expr),
     }
 }
 
 fn variables_declared(statement: &Statement) -> Vec<&str> {
-    match statement {
-        Statement::Noop => vec![],
-        Statement::Let(name, _) => vec![name.as_str()],
-        Statement::Return(_) => vec![],
-        Statement::Expression(_) => vec![],
-        Statement::Call(_, _) => vec![],
-        Statement::If(_, statements) => statements.iter().flat_map(variables_declared).collect(),
-        Statement::IfElse(_, if_statements, else_statements) => {
+    declarations(statement, true)
+}
+
+/// the variables a statement declares, counting assignments or not. an
+/// assignment in a lambda can be to a variable it captures.
+fn declarations(statement: &Statement, assignments: bool) -> Vec<&str> {
+    let declared = |statement| declarations(statement, assignments);
+
+    match &statement.kind {
+        StatementKind::Noop => vec![],
+        StatementKind::Let(name, _, _) => vec![name.as_str()],
+        StatementKind::Return(_) => vec![],
+        StatementKind::Expression(_) => vec![],
+        StatementKind::Call(_, _) => vec![],
+        StatementKind::If(_, statements) => statements.iter().flat_map(declared).collect(),
+        StatementKind::IfElse(_, if_statements, else_statements) => {
             let mut vars = vec![];
             for statement in if_statements {
-                vars.extend(variables_declared(statement));
+                vars.extend(declared(statement));
             }
             for statement in else_statements {
-                vars.extend(variables_declared(statement));
+                vars.extend(declared(statement));
             }
             vars
         }
-        Statement::ForLoop(init, _, update, statements) => {
-            let mut vars = variables_declared(init);
+        StatementKind::ForLoop(init, _, update, statements) => {
+            let mut vars = declared(init);
             for statement in statements {
-                vars.extend(variables_declared(statement));
+                vars.extend(declared(statement));
+            }
+            vars.extend(declared(update));
+            vars
+        }
+        StatementKind::ForIn(var, _, statements) => {
+            let mut vars = vec![var.as_str()];
+            vars.extend(statements.iter().flat_map(declared));
+            vars
+        }
+        StatementKind::While(_, statements) => statements.iter().flat_map(declared).collect(),
+        StatementKind::Break | StatementKind::Continue => vec![],
+        StatementKind::Assignment(name, _) if assignments => vec![name.as_str()],
+        StatementKind::Assignment(_, _) | StatementKind::GlobalAssignment(_, _) => vec![],
+        StatementKind::IndexAssignment(_, _, _) | StatementKind::FieldAssignment(_, _, _) => {
+            vec![]
+        }
+        StatementKind::Try(statements, catches) => {
+            let mut vars = statements.iter().flat_map(declared).collect::<Vec<_>>();
+            for catch in catches {
+                vars.push(catch.var.as_str());
+                vars.extend(catch.body.iter().flat_map(declared));
             }
-            vars.extend(variables_declared(update));
             vars
         }
-        Statement::Assignment(name, _) => vec![name.as_str()],
+        StatementKind::Raise(_) => vec![],
     }
 }
 
 fn variables_modified(statement: &Statement) -> Vec<&str> {
-    match statement {
-        Statement::Noop => vec![],
-        Statement::Return(_) => vec![],
-        Statement::Expression(_) => vec![],
-        Statement::Call(_, _) => vec![],
-        Statement::If(_, statements) => statements.iter().flat_map(variables_modified).collect(),
-        Statement::IfElse(_, if_statements, else_statements) => {
+    match &statement.kind {
+        StatementKind::Noop => vec![],
+        StatementKind::Return(_) => vec![],
+        StatementKind::Expression(_) => vec![],
+        StatementKind::Call(_, _) => vec![],
+        StatementKind::If(_, statements) => {
+            statements.iter().flat_map(variables_modified).collect()
+        }
+        StatementKind::IfElse(_, if_statements, else_statements) => {
             let mut vars = vec![];
             for statement in if_statements {
                 vars.extend(variables_modified(statement));
@@ -240,7 +1124,7 @@ fn variables_modified(statement: &Statement) -> Vec<&str> {
             }
             vars
         }
-        Statement::ForLoop(init, _, update, statements) => {
+        StatementKind::ForLoop(init, _, update, statements) => {
             let mut vars = variables_modified(init);
             for statement in statements {
                 vars.extend(variables_modified(statement));
@@ -248,145 +1132,594 @@ fn variables_modified(statement: &Statement) -> Vec<&str> {
             vars.extend(variables_modified(update));
             vars
         }
-        Statement::Assignment(name, _) | Statement::Let(name, _) => vec![name.as_str()],
+        StatementKind::ForIn(var, _, statements) => {
+            let mut vars = vec![var.as_str()];
+            vars.extend(statements.iter().flat_map(variables_modified));
+            vars
+        }
+        StatementKind::While(_, statements) => {
+            statements.iter().flat_map(variables_modified).collect()
+        }
+        StatementKind::Break | StatementKind::Continue => vec![],
+        StatementKind::Assignment(name, _) | StatementKind::Let(name, _, _) => vec![name.as_str()],
+        // globals aren't variables of the function
+        StatementKind::GlobalAssignment(_, _) => vec![],
+        StatementKind::IndexAssignment(value, _, _)
+        | StatementKind::FieldAssignment(value, _, _) => {
+            indexed_variable(value).into_iter().collect()
+        }
+        StatementKind::Try(statements, catches) => {
+            let mut vars = statements
+                .iter()
+                .flat_map(variables_modified)
+                .collect::<Vec<_>>();
+            for catch in catches {
+                vars.push(catch.var.as_str());
+                vars.extend(catch.body.iter().flat_map(variables_modified));
+            }
+            vars
+        }
+        StatementKind::Raise(_) => vec![],
+    }
+}
+
+/// the variable that an index or field assignment changes, e.g. `a` in
+/// `a[i].x = y`
+fn indexed_variable(expr: &Expr) -> Option<&str> {
+    match &expr.kind {
+        ExprKind::Variable(name) => Some(name.as_str()),
+        ExprKind::Index(value, _)
+        | ExprKind::Field(value, _)
+        | ExprKind::MethodCall(value, _, _) => indexed_variable(value),
+        _ => None,
     }
 }
 
 /// traverse a statement and return a list of variables used
 fn variables_used(expr: &Expr) -> Vec<&str> {
-    match expr {
-        Expr::Uninitialized => vec![],
-        Expr::Number(_) => vec![],
-        Expr::StringLiteral(_) => vec![],
-        Expr::Variable(name) => vec![name.as_str()],
-        Expr::Call(_, args) => args.iter().flat_map(|arg| variables_used(arg)).collect(),
-        Expr::Array(items) => items.iter().flat_map(|item| variables_used(item)).collect(),
-        Expr::Binary(lhs, _, rhs) => {
+    match &expr.kind {
+        ExprKind::Uninitialized => vec![],
+        ExprKind::Number(_) => vec![],
+        ExprKind::Float(_) => vec![],
+        ExprKind::Bool(_) | ExprKind::None => vec![],
+        ExprKind::StringLiteral(_) => vec![],
+        ExprKind::Variable(name) => vec![name.as_str()],
+        ExprKind::Call(_, args) | ExprKind::RegionCall(_, _, args) => {
+            args.iter().flat_map(|arg| variables_used(arg)).collect()
+        }
+        ExprKind::Array(items) => items.iter().flat_map(|item| variables_used(item)).collect(),
+        ExprKind::Dict(entries) => entries
+            .iter()
+            .flat_map(|(key, value)| {
+                let mut vars = variables_used(key);
+                vars.extend(variables_used(value));
+                vars
+            })
+            .collect(),
+        ExprKind::Binary(lhs, _, rhs) => {
             let mut vars = variables_used(lhs);
             vars.extend(variables_used(rhs));
             vars
         }
-        Expr::MethodCall(expr, _, args) => {
+        ExprKind::Unary(_, expr) => variables_used(expr),
+        ExprKind::Index(value, index) => {
+            let mut vars = variables_used(value);
+            vars.extend(variables_used(index));
+            vars
+        }
+        ExprKind::Field(value, _) => variables_used(value),
+        ExprKind::MethodCall(expr, _, args) | ExprKind::CallValue(expr, args) => {
             let mut vars = variables_used(expr);
             vars.extend(args.iter().flat_map(|arg| variables_used(arg)));
             vars
         }
+        ExprKind::Lambda(lambda) => lambda.captures.iter().map(String::as_str).collect(),
+        ExprKind::KeywordArgument(_, value) => variables_used(value),
+        ExprKind::FunctionValue(_, _) | ExprKind::Global(_) | ExprKind::Import(_) => vec![],
     }
 }
 
-fn simplify_statement(statement: &mut Statement) {
-    match statement {
-        Statement::Noop => {}
-        Statement::Let(_, expr) | Statement::Return(expr) => {
-            simplify_expression(expr);
+fn simplify_statement(statement: &mut Statement, numbers: &HashMap<String, Type>) {
+    match &mut statement.kind {
+        StatementKind::Noop => {}
+        StatementKind::Let(_, _, expr) | StatementKind::Return(expr) => {
+            simplify_expression(expr, numbers);
         }
-        Statement::Expression(expr) => {
-            simplify_expression(expr);
+        StatementKind::Expression(expr) => {
+            simplify_expression(expr, numbers);
 
             // if expression doesn't do anything, remove it
-            match expr.as_ref() {
-                Expr::Number(_) | Expr::StringLiteral(_) | Expr::Variable(_) => {
-                    *statement = Statement::Noop;
+            match &expr.kind {
+                ExprKind::Number(_)
+                | ExprKind::Float(_)
+                | ExprKind::Bool(_)
+                | ExprKind::None
+                | ExprKind::StringLiteral(_)
+                | ExprKind::Variable(_) => {
+                    statement.kind = StatementKind::Noop;
                 }
                 _ => {}
             }
         }
-        Statement::Call(_, args) => {
+        StatementKind::Call(_, args) => {
             for arg in args {
-                simplify_expression(arg);
+                simplify_expression(arg, numbers);
             }
         }
-        Statement::If(expr, statements) => {
-            simplify_expression(expr);
+        StatementKind::If(expr, statements) => {
+            simplify_expression(expr, numbers);
             for statement in statements {
-                simplify_statement(statement);
+                simplify_statement(statement, numbers);
             }
         }
-        Statement::IfElse(expr, if_statements, else_statements) => {
-            simplify_expression(expr);
+        StatementKind::IfElse(expr, if_statements, else_statements) => {
+            simplify_expression(expr, numbers);
             for statement in if_statements {
-                simplify_statement(statement);
+                simplify_statement(statement, numbers);
             }
             for statement in else_statements {
-                simplify_statement(statement);
+                simplify_statement(statement, numbers);
+            }
+        }
+        StatementKind::ForLoop(init, cond, update, statements) => {
+            simplify_statement(init, numbers);
+            simplify_expression(cond, numbers);
+            simplify_statement(update, numbers);
+            for statement in statements {
+                simplify_statement(statement, numbers);
+            }
+        }
+        StatementKind::ForIn(_, iter, statements) | StatementKind::While(iter, statements) => {
+            simplify_expression(iter, numbers);
+            for statement in statements {
+                simplify_statement(statement, numbers);
             }
         }
-        Statement::ForLoop(init, cond, update, statements) => {
-            simplify_statement(init);
-            simplify_expression(cond);
-            simplify_statement(update);
+        StatementKind::Break | StatementKind::Continue => {}
+        StatementKind::Assignment(_, expr) | StatementKind::GlobalAssignment(_, expr) => {
+            simplify_expression(expr, numbers);
+        }
+        StatementKind::IndexAssignment(value, index, expr) => {
+            simplify_expression(value, numbers);
+            simplify_expression(index, numbers);
+            simplify_expression(expr, numbers);
+        }
+        StatementKind::FieldAssignment(value, _, expr) => {
+            simplify_expression(value, numbers);
+            simplify_expression(expr, numbers);
+        }
+        StatementKind::Try(statements, catches) => {
             for statement in statements {
-                simplify_statement(statement);
+                simplify_statement(statement, numbers);
+            }
+            for catch in catches {
+                for statement in &mut catch.body {
+                    simplify_statement(statement, numbers);
+                }
+            }
+        }
+        StatementKind::Raise(expr) => {
+            simplify_expression(expr, numbers);
+        }
+    }
+}
+
+fn simplify_expression(expr: &mut Expr, numbers: &HashMap<String, Type>) {
+    match &mut expr.kind {
+        ExprKind::Binary(lhs, op, rhs) => {
+            simplify_expression(lhs, numbers);
+            simplify_expression(rhs, numbers);
+
+            // logic and equality can be folded for any constants
+            if let Some(kind) = fold_constant_logic(&lhs.kind, op, &rhs.kind) {
+                expr.kind = kind;
+                return;
+            }
+
+            // like in python, booleans are 0 and 1 in arithmetic and comparisons
+            match (bool_as_number(&lhs.kind), bool_as_number(&rhs.kind)) {
+                // constant folding
+                (ExprKind::Number(lhs), ExprKind::Number(rhs)) => {
+                    if let Some(kind) = fold_binary(lhs, op, rhs) {
+                        expr.kind = kind;
+                    }
+                }
+
+                // constant folding with floats: if either side is a float,
+                // so is the result
+                (lhs @ ExprKind::Float(_), rhs @ (ExprKind::Number(_) | ExprKind::Float(_)))
+                | (lhs @ ExprKind::Number(_), rhs @ ExprKind::Float(_)) => {
+                    if let Some(kind) = fold_float_binary(as_f64(&lhs), op, as_f64(&rhs)) {
+                        expr.kind = kind;
+                    }
+                }
+
+                // algebraic simplification: x + 0. a float stays the same
+                // except for -0.0, so only ints are simplified.
+                (ExprKind::Number(0), _)
+                    if *op == BinaryOp::Add && number_type(rhs, numbers) == Some(Type::Int) =>
+                {
+                    *expr = (**rhs).clone();
+                }
+                (_, ExprKind::Number(0))
+                    if *op == BinaryOp::Add && number_type(lhs, numbers) == Some(Type::Int) =>
+                {
+                    *expr = (**lhs).clone();
+                }
+
+                // algebraic simplification: x - 0
+                (_, ExprKind::Number(0))
+                    if *op == BinaryOp::Sub && is_int_or_float(lhs, numbers) =>
+                {
+                    *expr = (**lhs).clone();
+                }
+
+                // algebraic simplification: x * 0. a float times 0 is a float,
+                // so only ints are simplified.
+                (ExprKind::Number(0), _)
+                    if *op == BinaryOp::Mult && number_type(rhs, numbers) == Some(Type::Int) =>
+                {
+                    expr.kind = ExprKind::Number(0);
+                }
+                (_, ExprKind::Number(0))
+                    if *op == BinaryOp::Mult && number_type(lhs, numbers) == Some(Type::Int) =>
+                {
+                    expr.kind = ExprKind::Number(0);
+                }
+
+                // algebraic simplification: x * 1
+                (ExprKind::Number(1), _)
+                    if *op == BinaryOp::Mult && is_int_or_float(rhs, numbers) =>
+                {
+                    *expr = (**rhs).clone();
+                }
+                (_, ExprKind::Number(1))
+                    if *op == BinaryOp::Mult && is_int_or_float(lhs, numbers) =>
+                {
+                    *expr = (**lhs).clone();
+                }
+
+                // algebraic simplification: x / 1
+                (_, ExprKind::Number(1))
+                    if *op == BinaryOp::Div && is_int_or_float(lhs, numbers) =>
+                {
+                    *expr = (**lhs).clone();
+                }
+
+                _ => {}
+            }
+        }
+        ExprKind::Unary(op, operand) => {
+            simplify_expression(operand, numbers);
+
+            // constant folding
+            let folded = match (op, &operand.kind) {
+                (UnaryOp::Neg, ExprKind::Number(n)) => n.checked_neg().map(ExprKind::Number),
+                (UnaryOp::Neg, ExprKind::Float(Float(f))) => Some(ExprKind::Float(Float(-f))),
+                (UnaryOp::Neg, ExprKind::Bool(b)) => Some(ExprKind::Number(-(*b as i32))),
+                (UnaryOp::Not, kind) => constant_truthiness(kind).map(|t| ExprKind::Bool(!t)),
+                _ => None,
+            };
+
+            if let Some(kind) = folded {
+                expr.kind = kind;
+            }
+        }
+
+        ExprKind::Call(_, args) | ExprKind::RegionCall(_, _, args) => {
+            for arg in args {
+                simplify_expression(arg, numbers);
+            }
+        }
+        ExprKind::Array(items) => {
+            for item in items {
+                simplify_expression(item, numbers);
+            }
+        }
+        ExprKind::Dict(entries) => {
+            for (key, value) in entries {
+                simplify_expression(key, numbers);
+                simplify_expression(value, numbers);
+            }
+        }
+        ExprKind::MethodCall(expr, _, args) | ExprKind::CallValue(expr, args) => {
+            simplify_expression(expr, numbers);
+            for arg in args {
+                simplify_expression(arg, numbers);
+            }
+        }
+        ExprKind::Index(value, index) => {
+            simplify_expression(value, numbers);
+            simplify_expression(index, numbers);
+        }
+        ExprKind::Field(value, _) | ExprKind::KeywordArgument(_, value) => {
+            simplify_expression(value, numbers);
+        }
+
+        _ => {}
+    }
+}
+
+/// replace `if` statements whose condition is a constant with the branch that
+/// runs, and remove `while` loops that never run.
+fn remove_constant_branches(body: &mut Vec<Statement>) {
+    let mut new_body = vec![];
+
+    for mut statement in std::mem::take(body) {
+        match &mut statement.kind {
+            StatementKind::If(_, statements)
+            | StatementKind::ForLoop(_, _, _, statements)
+            | StatementKind::ForIn(_, _, statements)
+            | StatementKind::While(_, statements) => remove_constant_branches(statements),
+            StatementKind::IfElse(_, if_statements, else_statements) => {
+                remove_constant_branches(if_statements);
+                remove_constant_branches(else_statements);
+            }
+            StatementKind::Try(statements, catches) => {
+                remove_constant_branches(statements);
+                for catch in catches {
+                    remove_constant_branches(&mut catch.body);
+                }
             }
+            _ => {}
         }
-        Statement::Assignment(_, expr) => {
-            simplify_expression(expr);
+
+        let branch =
+            match &statement.kind {
+                StatementKind::If(cond, statements) => constant_truthiness(&cond.kind)
+                    .map(|truthy| if truthy { statements.clone() } else { vec![] }),
+                StatementKind::IfElse(cond, if_statements, else_statements) => {
+                    constant_truthiness(&cond.kind).map(|truthy| {
+                        if truthy {
+                            if_statements.clone()
+                        } else {
+                            else_statements.clone()
+                        }
+                    })
+                }
+                StatementKind::While(cond, _) if constant_truthiness(&cond.kind) == Some(false) => {
+                    Some(vec![])
+                }
+                _ => None,
+            };
+
+        let Some(branch) = branch else {
+            new_body.push(statement);
+            continue;
+        };
+
+        // variables declared in the branch are only in scope inside it, so
+        // then it has to stay in its own block, which is an `if true`
+        let declares_variables = branch
+            .iter()
+            .any(|statement| matches!(statement.kind, StatementKind::Let(_, _, _)));
+        if declares_variables {
+            let always = Expr::new(ExprKind::Bool(true), statement.span);
+            statement.kind = StatementKind::If(Box::new(always), branch);
+            new_body.push(statement);
+        } else {
+            new_body.extend(branch);
+        }
+    }
+
+    *body = new_body;
+}
+
+/// evaluate a binary operation on two constants. arithmetic gives a number
+/// and everything else gives a bool. like in python, division rounds down and
+/// a modulo has the sign of the divisor. returns None if it can't be done at
+/// compile time, i.e. it overflows or divides by zero.
+fn fold_binary(lhs: i32, op: &BinaryOp, rhs: i32) -> Option<ExprKind> {
+    let bool_result = |b: bool| Some(ExprKind::Bool(b));
+
+    let result = match op {
+        BinaryOp::Add => lhs.checked_add(rhs),
+        BinaryOp::Sub => lhs.checked_sub(rhs),
+        BinaryOp::Mult => lhs.checked_mul(rhs),
+        BinaryOp::Div => lhs.checked_div(rhs).map(|quotient| {
+            if lhs % rhs != 0 && (lhs < 0) != (rhs < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }),
+        BinaryOp::Mod => lhs.checked_rem(rhs).map(|remainder| {
+            if remainder != 0 && (remainder < 0) != (rhs < 0) {
+                remainder + rhs
+            } else {
+                remainder
+            }
+        }),
+        BinaryOp::Equal => return bool_result(lhs == rhs),
+        BinaryOp::NotEqual => return bool_result(lhs != rhs),
+        BinaryOp::LessThan => return bool_result(lhs < rhs),
+        BinaryOp::GreaterThan => return bool_result(lhs > rhs),
+        BinaryOp::LessThanOrEqual => return bool_result(lhs <= rhs),
+        BinaryOp::GreaterThanOrEqual => return bool_result(lhs >= rhs),
+        BinaryOp::And => return bool_result(lhs != 0 && rhs != 0),
+        BinaryOp::Or => return bool_result(lhs != 0 || rhs != 0),
+    };
+
+    result.map(ExprKind::Number)
+}
+
+/// evaluate a binary operation on two float constants. arithmetic gives a
+/// float and everything else gives a bool. returns None if the result isn't a
+/// finite number, e.g. when dividing by zero, so it's left for the runtime.
+fn fold_float_binary(lhs: f64, op: &BinaryOp, rhs: f64) -> Option<ExprKind> {
+    let bool_result = |b: bool| Some(ExprKind::Bool(b));
+
+    let result = match op {
+        BinaryOp::Add => lhs + rhs,
+        BinaryOp::Sub => lhs - rhs,
+        BinaryOp::Mult => lhs * rhs,
+        BinaryOp::Div => lhs / rhs,
+        BinaryOp::Mod => match lhs % rhs {
+            remainder if remainder != 0.0 && (remainder < 0.0) != (rhs < 0.0) => remainder + rhs,
+            remainder => remainder,
+        },
+        BinaryOp::Equal => return bool_result(lhs == rhs),
+        BinaryOp::NotEqual => return bool_result(lhs != rhs),
+        BinaryOp::LessThan => return bool_result(lhs < rhs),
+        BinaryOp::GreaterThan => return bool_result(lhs > rhs),
+        BinaryOp::LessThanOrEqual => return bool_result(lhs <= rhs),
+        BinaryOp::GreaterThanOrEqual => return bool_result(lhs >= rhs),
+        BinaryOp::And => return bool_result(lhs != 0.0 && rhs != 0.0),
+        BinaryOp::Or => return bool_result(lhs != 0.0 || rhs != 0.0),
+    };
+
+    result.is_finite().then_some(ExprKind::Float(Float(result)))
+}
+
+/// evaluate `&&`, `||`, `==` and `!=` on constants of any type. `false && x`
+/// and `true || x` never evaluate `x`, so it doesn't have to be a constant.
+fn fold_constant_logic(lhs: &ExprKind, op: &BinaryOp, rhs: &ExprKind) -> Option<ExprKind> {
+    let result = match op {
+        BinaryOp::And => constant_truthiness(lhs)? && constant_truthiness(rhs)?,
+        BinaryOp::Or => constant_truthiness(lhs)? || constant_truthiness(rhs)?,
+        BinaryOp::Equal => constant_equality(lhs, rhs)?,
+        BinaryOp::NotEqual => !constant_equality(lhs, rhs)?,
+        _ => return None,
+    };
+
+    Some(ExprKind::Bool(result))
+}
+
+/// whether a constant is truthy, using python's rules. returns None if it
+/// isn't a constant.
+fn constant_truthiness(kind: &ExprKind) -> Option<bool> {
+    match kind {
+        ExprKind::Number(n) => Some(*n != 0),
+        ExprKind::Float(Float(f)) => Some(*f != 0.0),
+        ExprKind::Bool(b) => Some(*b),
+        ExprKind::None => Some(false),
+        ExprKind::StringLiteral(s) => Some(!s.is_empty()),
+        _ => None,
+    }
+}
+
+/// whether two constants are equal, using python's rules, e.g. `1 == 1.0` and
+/// `true == 1`. returns None if either of them isn't a constant.
+fn constant_equality(lhs: &ExprKind, rhs: &ExprKind) -> Option<bool> {
+    match (bool_as_number(lhs), bool_as_number(rhs)) {
+        (ExprKind::None, ExprKind::None) => Some(true),
+        (ExprKind::StringLiteral(a), ExprKind::StringLiteral(b)) => Some(a == b),
+        (
+            lhs @ (ExprKind::Number(_) | ExprKind::Float(_)),
+            rhs @ (ExprKind::Number(_) | ExprKind::Float(_)),
+        ) => Some(as_f64(&lhs) == as_f64(&rhs)),
+
+        // constants of different types are never equal
+        (lhs, rhs) => {
+            let both_constant =
+                constant_truthiness(&lhs).is_some() && constant_truthiness(&rhs).is_some();
+            both_constant.then_some(false)
         }
     }
 }
 
-fn simplify_expression(expr: &mut Expr) {
-    match expr {
-        Expr::Binary(rhs, op, lhs) => {
-            simplify_expression(lhs);
-            simplify_expression(rhs);
+/// booleans are numbers in arithmetic, so turn a bool constant into 0 or 1
+fn bool_as_number(kind: &ExprKind) -> ExprKind {
+    match kind {
+        ExprKind::Bool(b) => ExprKind::Number(*b as i32),
+        kind => kind.clone(),
+    }
+}
 
-            match (lhs.as_ref(), rhs.as_ref()) {
-                // constant folding
-                (Expr::Number(lhs), Expr::Number(rhs)) => {
-                    *expr = Expr::Number(match op {
-                        BinaryOp::Add => *lhs + *rhs,
-                        BinaryOp::Mult => *lhs * *rhs,
-                        BinaryOp::LessThan => (lhs < rhs) as i32,
-                    });
-                }
+/// the value of a number or float constant, as a float
+fn as_f64(kind: &ExprKind) -> f64 {
+    match kind {
+        ExprKind::Number(n) => *n as f64,
+        ExprKind::Float(Float(f)) => *f,
+        _ => unreachable!("not a number: {kind:?}"),
+    }
+}
 
-                // algebraic simplification: x + 0
-                (Expr::Number(0), _) if *op == BinaryOp::Add => {
-                    *expr = (**rhs).clone();
-                }
-                (_, Expr::Number(0)) if *op == BinaryOp::Add => {
-                    *expr = (**lhs).clone();
-                }
+/// the variables of a function that always hold a number, because every
+/// declaration of them has the same int, float or bool type. assigning to
+/// them converts the value to that type.
+fn number_variables(function: &Function) -> HashMap<String, Type> {
+    let params = function
+        .params
+        .iter()
+        .map(|param| (param.name.as_str(), param.var_type.as_ref()));
+    let declarations = function
+        .body
+        .iter()
+        .flat_map(statement_and_nested)
+        .flat_map(|statement| match &statement.kind {
+            StatementKind::Let(name, var_type, _) => vec![(name.as_str(), var_type.as_ref())],
+            StatementKind::ForIn(var, _, _) => vec![(var.as_str(), None)],
+            StatementKind::Try(_, catches) => catches
+                .iter()
+                .map(|catch| (catch.var.as_str(), None))
+                .collect(),
+            _ => vec![],
+        });
 
-                // algebraic simplification: x * 0
-                (Expr::Number(0), _) | (_, Expr::Number(0)) if *op == BinaryOp::Mult => {
-                    *expr = Expr::Number(0);
+    let mut types: HashMap<String, Option<Type>> = HashMap::new();
+    for (name, var_type) in params.chain(declarations) {
+        let var_type = var_type
+            .filter(|t| matches!(t, Type::Int | Type::Float | Type::Bool))
+            .cloned();
+        types
+            .entry(name.to_string())
+            .and_modify(|t| {
+                if *t != var_type {
+                    *t = None;
                 }
+            })
+            .or_insert(var_type);
+    }
 
-                // algebraic simplification: x * 1
-                (Expr::Number(1), _) if *op == BinaryOp::Mult => {
-                    *expr = (**rhs).clone();
+    types
+        .into_iter()
+        .filter_map(|(name, var_type)| Some((name, var_type?)))
+        .collect()
+}
+
+/// the type of an expression if it's always a number: an int, a float or a
+/// bool. arithmetic on two ints or bools gives an int, and on a float gives a
+/// float. comparisons and logic give a bool.
+fn number_type(expr: &Expr, numbers: &HashMap<String, Type>) -> Option<Type> {
+    match &expr.kind {
+        ExprKind::Number(_) => Some(Type::Int),
+        ExprKind::Float(_) => Some(Type::Float),
+        ExprKind::Bool(_) => Some(Type::Bool),
+        ExprKind::Variable(name) => numbers.get(name).cloned(),
+        ExprKind::Binary(lhs, op, rhs) => {
+            let lhs = number_type(lhs, numbers)?;
+            let rhs = number_type(rhs, numbers)?;
+            match op {
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mult | BinaryOp::Div | BinaryOp::Mod
+                    if lhs == Type::Float || rhs == Type::Float =>
+                {
+                    Some(Type::Float)
                 }
-                (_, Expr::Number(1)) if *op == BinaryOp::Mult => {
-                    *expr = (**lhs).clone();
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mult | BinaryOp::Div | BinaryOp::Mod => {
+                    Some(Type::Int)
                 }
-
-                _ => {}
-            }
-        }
-
-        Expr::Call(_, args) => {
-            for arg in args {
-                simplify_expression(arg);
-            }
-        }
-        Expr::Array(items) => {
-            for item in items {
-                simplify_expression(item);
+                _ => Some(Type::Bool),
             }
         }
-        Expr::MethodCall(expr, _, args) => {
-            simplify_expression(expr);
-            for arg in args {
-                simplify_expression(arg);
-            }
+        ExprKind::Unary(UnaryOp::Neg, operand) => match number_type(operand, numbers)? {
+            Type::Bool => Some(Type::Int),
+            number => Some(number),
+        },
+        ExprKind::Unary(UnaryOp::Not, operand) => {
+            number_type(operand, numbers)?;
+            Some(Type::Bool)
         }
-
-        _ => {}
+        _ => None,
     }
 }
 
+/// check if an expression is always an int or a float, so arithmetic that
+/// doesn't change its value can be left out
+fn is_int_or_float(expr: &Expr, numbers: &HashMap<String, Type>) -> bool {
+    matches!(number_type(expr, numbers), Some(Type::Int | Type::Float))
+}
+
 #[derive(Debug, Clone, Default)]
 struct SubexprGraph {
     subexprs: Vec<(String, Expr)>,
@@ -405,17 +1738,27 @@ impl SubexprGraph {
     fn variable_modified(&mut self, var: impl Into<String>, new_expr: Expr) {
         let var: String = var.into();
 
+        self.forget_variable(&var);
+        self.subexprs.push((var, new_expr));
+    }
+
+    /// the variable has changed to an unknown value, e.g. in a loop
+    fn forget_variable(&mut self, var: &str) {
         self.subexprs.retain(|(name, expr)| {
             // remove the variable itself
-            if name == &var {
+            if name == var {
                 return false;
             }
 
             // remove all expressions that depend on the variable
-            !variables_used(expr).contains(&var.as_ref())
+            !variables_used(expr).contains(&var)
         });
+    }
 
-        self.subexprs.push((var, new_expr));
+    /// a list, buffer or object has changed, so indexing or reading a field
+    /// might give a different value
+    fn forget_indexing(&mut self) {
+        self.subexprs.retain(|(_, expr)| !reads_memory(expr));
     }
 }
 
@@ -425,36 +1768,47 @@ fn eliminate_common_subexpressions(body: &mut [Statement], mut subexprs: Subexpr
     };
 
     let replace_if_repeated = |expr: &mut Expr| {
+        // a constant is already as cheap as a variable, a new list or dict
+        // can't be shared with another variable, and a method or a global
+        // could give a different result each time
+        if constant_truthiness(&expr.kind).is_some()
+            || makes_new_object(expr)
+            || calls_unknown_code(expr)
+            || reads_global(expr)
+        {
+            return;
+        }
+
         if let Some(var) = subexprs.expr_is_repeated(expr) {
-            *expr = Expr::Variable(var);
+            expr.kind = ExprKind::Variable(var);
         }
     };
 
-    match head {
-        Statement::Let(name, expr) | Statement::Assignment(name, expr) => {
+    match &mut head.kind {
+        StatementKind::Let(name, _, expr) | StatementKind::Assignment(name, expr) => {
             let name = name.clone();
 
             replace_if_repeated(expr);
             subexprs.variable_modified(name, expr.as_ref().clone());
         }
-        Statement::Return(expr) | Statement::Expression(expr) => {
+        StatementKind::Return(expr) | StatementKind::Expression(expr) => {
             replace_if_repeated(expr);
         }
-        Statement::Call(_, args) => {
+        StatementKind::Call(_, args) => {
             for arg in args {
                 replace_if_repeated(arg);
             }
         }
-        Statement::If(expr, statements) => {
+        StatementKind::If(expr, statements) => {
             replace_if_repeated(expr);
             eliminate_common_subexpressions(statements, subexprs.clone());
         }
-        Statement::IfElse(expr, if_statements, else_statements) => {
+        StatementKind::IfElse(expr, if_statements, else_statements) => {
             replace_if_repeated(expr);
             eliminate_common_subexpressions(if_statements, subexprs.clone());
             eliminate_common_subexpressions(else_statements, subexprs.clone());
         }
-        Statement::ForLoop(init, cond, update, statements) => {
+        StatementKind::ForLoop(init, cond, update, statements) => {
             replace_if_repeated(cond);
 
             let mut loop_body = vec![init.as_ref().clone(), update.as_ref().clone()];
@@ -464,74 +1818,160 @@ fn eliminate_common_subexpressions(body: &mut [Statement], mut subexprs: Subexpr
 
             *statements = loop_body[2..].to_vec();
         }
-        Statement::Noop => {}
+        StatementKind::ForIn(var, iter, statements) => {
+            // the iterable is only evaluated once, before the loop
+            replace_if_repeated(iter);
+
+            subexprs.forget_variable(var);
+            for var in statements.iter().flat_map(variables_modified) {
+                subexprs.forget_variable(var);
+            }
+            eliminate_common_subexpressions(statements, subexprs.clone());
+        }
+        StatementKind::While(cond, statements) => {
+            // anything modified in the loop could have a different value at
+            // the start of each iteration, and after the loop
+            for var in statements.iter().flat_map(variables_modified) {
+                subexprs.forget_variable(var);
+            }
+
+            if let Some(var) = subexprs
+                .expr_is_repeated(cond)
+                .filter(|_| !reads_global(cond))
+            {
+                cond.kind = ExprKind::Variable(var);
+            }
+            eliminate_common_subexpressions(statements, subexprs.clone());
+        }
+        StatementKind::IndexAssignment(value, index, expr) => {
+            replace_if_repeated(index);
+            replace_if_repeated(expr);
+
+            // the list or buffer could be shared with other variables, so
+            // any indexing might give a different value now
+            if let Some(var) = indexed_variable(value) {
+                subexprs.forget_variable(var);
+            }
+            subexprs.forget_indexing();
+        }
+        StatementKind::FieldAssignment(value, _, expr) => {
+            replace_if_repeated(expr);
+
+            // same as above, objects can be shared too
+            if let Some(var) = indexed_variable(value) {
+                subexprs.forget_variable(var);
+            }
+            subexprs.forget_indexing();
+        }
+        StatementKind::Try(statements, catches) => {
+            eliminate_common_subexpressions(statements, subexprs.clone());
+
+            // the try block could stop anywhere, so a catch only knows what
+            // was true before it. after the try, any of it could have run.
+            for var in statements.iter().flat_map(variables_modified) {
+                subexprs.forget_variable(var);
+            }
+            for catch in catches.iter_mut() {
+                let mut catch_subexprs = subexprs.clone();
+                catch_subexprs.forget_variable(&catch.var);
+                eliminate_common_subexpressions(&mut catch.body, catch_subexprs);
+            }
+            for catch in catches.iter() {
+                subexprs.forget_variable(&catch.var);
+                for var in catch.body.iter().flat_map(variables_modified) {
+                    subexprs.forget_variable(var);
+                }
+            }
+        }
+        StatementKind::Raise(expr) | StatementKind::GlobalAssignment(_, expr) => {
+            replace_if_repeated(expr);
+        }
+        StatementKind::Noop | StatementKind::Break | StatementKind::Continue => {}
+    }
+
+    // a method or function value can change the fields of its object, or
+    // of anything else
+    let calls_method = exprs_in_statment(head).into_iter().any(calls_unknown_code);
+    if calls_method {
+        subexprs.forget_indexing();
     }
 
     eliminate_common_subexpressions(&mut body[1..], subexprs);
 }
 
-fn loop_invariant_motion(body: &mut Vec<Statement>) {
+fn loop_invariant_motion(
+    body: &mut Vec<Statement>,
+    numbers: &HashMap<String, Type>,
+    taken_names: &[String],
+) {
     let mut new_body = vec![];
 
     for mut fn_statement in body.clone() {
-        match &mut fn_statement {
-            Statement::ForLoop(init, _cond, update, loop_statements) => {
-                let exprs_in_loop = loop_statements
-                    .iter()
-                    .flat_map(exprs_in_statment)
-                    .collect::<Vec<_>>();
-
-                let modified_variables = variables_modified(&init)
+        match &mut fn_statement.kind {
+            StatementKind::ForLoop(init, _cond, update, loop_statements) => {
+                let modified_variables = variables_modified(init)
                     .into_iter()
                     .chain(loop_statements.iter().flat_map(variables_modified))
                     .chain(variables_modified(update))
+                    .map(String::from)
                     .collect::<Vec<_>>();
 
-                let mut invariant_exprs = HashSet::new();
-                for expr in exprs_in_loop {
-                    if let Expr::Variable(_) | Expr::Number(_) | Expr::StringLiteral(_) =
-                        expr.as_ref()
-                    {
-                        continue;
-                    }
+                new_body.extend(hoist_loop_invariants(
+                    loop_statements,
+                    &modified_variables,
+                    numbers,
+                    taken_names,
+                ));
+                new_body.push(fn_statement);
+            }
 
-                    let vars_used_in_expr = variables_used(expr);
-                    if vars_used_in_expr
-                        .iter()
-                        .all(|var| !modified_variables.contains(var))
-                    {
-                        invariant_exprs.insert(expr);
-                    }
-                }
+            StatementKind::ForIn(var, _iter, loop_statements) => {
+                let modified_variables = std::iter::once(var.as_str())
+                    .chain(loop_statements.iter().flat_map(variables_modified))
+                    .map(String::from)
+                    .collect::<Vec<_>>();
 
-                // invariant expressions are detected, now create a temp
-                // variable for them and replace their uses
-                let temp_vars = invariant_exprs
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, expr)| {
-                        let temp_var = format!("<Identifier, __temp_{}>", i);
-                        (expr.as_ref().clone(), temp_var)
-                    })
-                    .collect::<HashMap<_, _>>();
+                new_body.extend(hoist_loop_invariants(
+                    loop_statements,
+                    &modified_variables,
+                    numbers,
+                    taken_names,
+                ));
+                new_body.push(fn_statement);
+            }
 
-                for loop_statement in loop_statements {
-                    run_on_all_exprs(loop_statement, |expr| {
-                        if let Some(temp_var) = temp_vars.get(expr) {
-                            *expr = Expr::Variable(temp_var.clone());
-                        }
-                    });
-                }
+            StatementKind::While(_cond, loop_statements) => {
+                let modified_variables = loop_statements
+                    .iter()
+                    .flat_map(variables_modified)
+                    .map(String::from)
+                    .collect::<Vec<_>>();
 
-                // add temp variables before the loop
-                for (expr, temp_var) in temp_vars {
-                    new_body.push(Statement::Let(temp_var, Box::new(expr)));
-                }
+                new_body.extend(hoist_loop_invariants(
+                    loop_statements,
+                    &modified_variables,
+                    numbers,
+                    taken_names,
+                ));
+                new_body.push(fn_statement);
+            }
+
+            StatementKind::If(_, statements) => {
+                loop_invariant_motion(statements, numbers, taken_names);
+                new_body.push(fn_statement);
+            }
+
+            StatementKind::IfElse(_, if_statements, else_statements) => {
+                loop_invariant_motion(if_statements, numbers, taken_names);
+                loop_invariant_motion(else_statements, numbers, taken_names);
                 new_body.push(fn_statement);
             }
 
-            Statement::If(_, statements) | Statement::IfElse(_, statements, _) => {
-                loop_invariant_motion(statements);
+            StatementKind::Try(statements, catches) => {
+                loop_invariant_motion(statements, numbers, taken_names);
+                for catch in catches {
+                    loop_invariant_motion(&mut catch.body, numbers, taken_names);
+                }
                 new_body.push(fn_statement);
             }
 
@@ -544,30 +1984,282 @@ fn loop_invariant_motion(body: &mut Vec<Statement>) {
     *body = new_body;
 }
 
+/// replace the expressions in a loop body that don't use any of the variables
+/// modified in the loop with temp variables. returns the `let` statements for
+/// the temp variables, which go before the loop. the temp variables are named
+/// `__temp_N`, skipping the taken names.
+fn hoist_loop_invariants(
+    loop_statements: &mut [Statement],
+    modified_variables: &[String],
+    numbers: &HashMap<String, Type>,
+    taken_names: &[String],
+) -> Vec<Statement> {
+    // an expression moved out of a try block wouldn't be caught anymore
+    if contains_try(loop_statements) {
+        return vec![];
+    }
+
+    // the loop body might not run at all, so an expression that can raise an
+    // exception has to stay in it
+    let exprs_in_loop = loop_statements
+        .iter()
+        .flat_map(hoistable_exprs)
+        .filter(|expr| !can_raise(expr, numbers));
+
+    let mut invariant_exprs = HashSet::new();
+    for expr in exprs_in_loop {
+        // a keyword argument has to stay in its call, only its value can be
+        // moved out. a variable declared without a value gets a new none
+        // every time.
+        if let ExprKind::Variable(_)
+        | ExprKind::Import(_)
+        | ExprKind::KeywordArgument(_, _)
+        | ExprKind::Uninitialized
+        | ExprKind::Number(_)
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::None
+        | ExprKind::StringLiteral(_) = &expr.kind
+        {
+            continue;
+        }
+
+        // indexing and fields can fail or be changed through another
+        // variable, so they have to stay in the loop. each iteration needs
+        // its own new list or dict too, and its own method calls. globals
+        // can be changed by any call.
+        if reads_memory(expr)
+            || makes_new_object(expr)
+            || calls_unknown_code(expr)
+            || reads_global(expr)
+        {
+            continue;
+        }
+
+        let vars_used_in_expr = variables_used(expr);
+        if vars_used_in_expr
+            .iter()
+            .all(|var| !modified_variables.iter().any(|modified| modified == var))
+        {
+            invariant_exprs.insert(expr.clone());
+        }
+    }
+
+    // invariant expressions are detected, now create a temp
+    // variable for them and replace their uses
+    let temp_names = (0..)
+        .map(|i| format!("__temp_{}", i))
+        .filter(|name| !taken_names.contains(name));
+    let temp_vars = invariant_exprs
+        .into_iter()
+        .zip(temp_names)
+        .collect::<HashMap<_, _>>();
+
+    for loop_statement in loop_statements {
+        run_on_all_exprs(loop_statement, |expr| {
+            if let Some(temp_var) = temp_vars.get(expr) {
+                expr.kind = ExprKind::Variable(temp_var.clone());
+            }
+        });
+    }
+
+    // the temp variables are declared before the loop
+    temp_vars
+        .into_iter()
+        .map(|(expr, temp_var)| {
+            let span = expr.span;
+            let temp_let = StatementKind::Let(temp_var, None, Box::new(expr));
+            Statement::new(temp_let, span)
+        })
+        .collect()
+}
+
+/// the expressions in a loop body statement that could be moved out of the
+/// loop. the ones in an if or else body only run sometimes, so they stay in
+/// the loop.
+fn hoistable_exprs(statement: &Statement) -> Vec<&Expr> {
+    match &statement.kind {
+        StatementKind::If(cond, _) | StatementKind::IfElse(cond, _, _) => {
+            expr_and_nested_exprs(cond)
+        }
+        StatementKind::While(cond, statements) | StatementKind::ForIn(_, cond, statements) => {
+            let mut exprs = expr_and_nested_exprs(cond);
+            exprs.extend(statements.iter().flat_map(hoistable_exprs));
+            exprs
+        }
+        StatementKind::ForLoop(init, cond, update, statements) => {
+            let mut exprs = hoistable_exprs(init);
+            exprs.extend(expr_and_nested_exprs(cond));
+            exprs.extend(hoistable_exprs(update));
+            exprs.extend(statements.iter().flat_map(hoistable_exprs));
+            exprs
+        }
+        StatementKind::Try(_, _) => vec![],
+        _ => exprs_in_statment(statement),
+    }
+}
+
+/// whether an expression can raise an exception: arithmetic, comparisons and
+/// logic on values that might not be numbers, dividing by something that
+/// isn't a constant, indexing, or calling a function
+fn can_raise(expr: &Expr, numbers: &HashMap<String, Type>) -> bool {
+    expr_and_nested_exprs(expr).iter().any(|e| match &e.kind {
+        // the smallest int divided by -1 doesn't fit in an int
+        ExprKind::Binary(_, BinaryOp::Div | BinaryOp::Mod, rhs)
+            if !matches!(&rhs.kind, ExprKind::Number(n) if *n != 0 && *n != -1)
+                && !matches!(&rhs.kind, ExprKind::Float(Float(f)) if *f != 0.0) =>
+        {
+            true
+        }
+        ExprKind::Binary(lhs, _, rhs) => {
+            number_type(lhs, numbers).is_none() || number_type(rhs, numbers).is_none()
+        }
+        ExprKind::Unary(_, operand) => number_type(operand, numbers).is_none(),
+        ExprKind::Index(_, _)
+        | ExprKind::Call(_, _)
+        | ExprKind::RegionCall(_, _, _)
+        | ExprKind::MethodCall(_, _, _)
+        | ExprKind::CallValue(_, _) => true,
+        _ => false,
+    })
+}
+
+/// whether an expression indexes a value or reads a field, which can give a
+/// different result even if none of the variables it uses have changed
+fn reads_memory(expr: &Expr) -> bool {
+    expr_and_nested_exprs(expr)
+        .iter()
+        .any(|e| matches!(e.kind, ExprKind::Index(_, _) | ExprKind::Field(_, _)))
+}
+
+/// whether an expression makes a new list, dict, lambda, buffer or
+/// exception, which can be changed later or has its own copy of the
+/// variables it uses
+fn makes_new_object(expr: &Expr) -> bool {
+    expr_and_nested_exprs(expr).iter().any(|e| match &e.kind {
+        ExprKind::Array(_) | ExprKind::Dict(_) | ExprKind::Lambda(_) => true,
+        ExprKind::Call(name, _) => {
+            NEW_OBJECT_FUNCTIONS.contains(&name.as_str()) || EXCEPTIONS.contains(&name.as_str())
+        }
+        _ => false,
+    })
+}
+
+/// whether an expression calls a method, a function value, a function from
+/// the source code, or `free` or `blackbox`, which could do anything (like
+/// change a global), so it has to run every time
+fn calls_unknown_code(expr: &Expr) -> bool {
+    expr_and_nested_exprs(expr).iter().any(|e| match &e.kind {
+        ExprKind::MethodCall(_, _, _)
+        | ExprKind::CallValue(_, _)
+        | ExprKind::RegionCall(_, _, _) => true,
+        ExprKind::Call(name, _) => {
+            !NEW_OBJECT_FUNCTIONS.contains(&name.as_str()) && !EXCEPTIONS.contains(&name.as_str())
+        }
+        _ => false,
+    })
+}
+
+/// the functions built into the language that only use their arguments, and
+/// make a new buffer or list. `free` and `blackbox` are like unknown code.
+const NEW_OBJECT_FUNCTIONS: &[&str] = &["allocate", "range"];
+
+/// whether an expression reads one of the region's globals, which any
+/// function could change
+fn reads_global(expr: &Expr) -> bool {
+    expr_and_nested_exprs(expr)
+        .iter()
+        .any(|e| matches!(e.kind, ExprKind::Global(_)))
+}
+
+/// a statement and the statements nested in it
+fn statement_and_nested(statement: &Statement) -> Vec<&Statement> {
+    let nested = match &statement.kind {
+        StatementKind::If(_, statements)
+        | StatementKind::ForIn(_, _, statements)
+        | StatementKind::While(_, statements) => statements.iter().collect(),
+        StatementKind::IfElse(_, if_statements, else_statements) => {
+            if_statements.iter().chain(else_statements).collect()
+        }
+        StatementKind::ForLoop(init, _, update, statements) => std::iter::once(&**init)
+            .chain(statements)
+            .chain(std::iter::once(&**update))
+            .collect(),
+        StatementKind::Try(statements, catches) => statements
+            .iter()
+            .chain(catches.iter().flat_map(|catch| &catch.body))
+            .collect(),
+        _ => vec![],
+    };
+
+    let mut statements = vec![statement];
+    statements.extend(nested.into_iter().flat_map(statement_and_nested));
+    statements
+}
+
+/// run a function on a statement and on the statements nested in it
+fn run_on_all_statements(statement: &mut Statement, f: &mut impl FnMut(&mut Statement)) {
+    f(statement);
+
+    match &mut statement.kind {
+        StatementKind::If(_, statements)
+        | StatementKind::ForIn(_, _, statements)
+        | StatementKind::While(_, statements) => {
+            for statement in statements {
+                run_on_all_statements(statement, f);
+            }
+        }
+        StatementKind::IfElse(_, if_statements, else_statements) => {
+            for statement in if_statements.iter_mut().chain(else_statements) {
+                run_on_all_statements(statement, f);
+            }
+        }
+        StatementKind::ForLoop(init, _, update, statements) => {
+            run_on_all_statements(init, f);
+            for statement in statements {
+                run_on_all_statements(statement, f);
+            }
+            run_on_all_statements(update, f);
+        }
+        StatementKind::Try(statements, catches) => {
+            for statement in statements {
+                run_on_all_statements(statement, f);
+            }
+            for catch in catches {
+                for statement in &mut catch.body {
+                    run_on_all_statements(statement, f);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
 fn run_on_all_exprs<F>(statement: &mut Statement, f: F)
 where
     F: FnMut(&mut Expr) + Copy,
 {
-    match statement {
-        Statement::Noop => {}
-        Statement::Let(_, expr)
-        | Statement::Return(expr)
-        | Statement::Expression(expr)
-        | Statement::Assignment(_, expr) => {
+    match &mut statement.kind {
+        StatementKind::Noop => {}
+        StatementKind::Let(_, _, expr)
+        | StatementKind::Return(expr)
+        | StatementKind::Expression(expr)
+        | StatementKind::Assignment(_, expr)
+        | StatementKind::GlobalAssignment(_, expr) => {
             run_on_expr_and_nested(expr, f);
         }
-        Statement::Call(_, args) => {
+        StatementKind::Call(_, args) => {
             for arg in args {
                 run_on_expr_and_nested(arg, f);
             }
         }
-        Statement::If(expr, statements) => {
+        StatementKind::If(expr, statements) => {
             run_on_expr_and_nested(expr, f);
             for statement in statements {
                 run_on_all_exprs(statement, f);
             }
         }
-        Statement::IfElse(expr, if_statements, else_statements) => {
+        StatementKind::IfElse(expr, if_statements, else_statements) => {
             run_on_expr_and_nested(expr, f);
             for statement in if_statements {
                 run_on_all_exprs(statement, f);
@@ -576,7 +2268,7 @@ where
                 run_on_all_exprs(statement, f);
             }
         }
-        Statement::ForLoop(init, cond, update, statements) => {
+        StatementKind::ForLoop(init, cond, update, statements) => {
             run_on_all_exprs(init, f);
             run_on_expr_and_nested(cond, f);
             for statement in statements {
@@ -584,6 +2276,35 @@ where
             }
             run_on_all_exprs(update, f);
         }
+        StatementKind::ForIn(_, iter, statements) | StatementKind::While(iter, statements) => {
+            run_on_expr_and_nested(iter, f);
+            for statement in statements {
+                run_on_all_exprs(statement, f);
+            }
+        }
+        StatementKind::Break | StatementKind::Continue => {}
+        StatementKind::IndexAssignment(value, index, expr) => {
+            run_on_expr_and_nested(value, f);
+            run_on_expr_and_nested(index, f);
+            run_on_expr_and_nested(expr, f);
+        }
+        StatementKind::FieldAssignment(value, _, expr) => {
+            run_on_expr_and_nested(value, f);
+            run_on_expr_and_nested(expr, f);
+        }
+        StatementKind::Try(statements, catches) => {
+            for statement in statements {
+                run_on_all_exprs(statement, f);
+            }
+            for catch in catches {
+                for statement in &mut catch.body {
+                    run_on_all_exprs(statement, f);
+                }
+            }
+        }
+        StatementKind::Raise(expr) => {
+            run_on_expr_and_nested(expr, f);
+        }
     }
 }
 
@@ -593,43 +2314,87 @@ where
 {
     f(expr);
 
-    match expr {
-        Expr::Uninitialized => {}
-        Expr::Number(_) => {}
-        Expr::StringLiteral(_) => {}
-        Expr::Variable(_) => {}
-        Expr::Call(_, args) => {
+    match &mut expr.kind {
+        ExprKind::Uninitialized => {}
+        ExprKind::Number(_) => {}
+        ExprKind::Float(_) => {}
+        ExprKind::Bool(_) | ExprKind::None => {}
+        ExprKind::StringLiteral(_) => {}
+        ExprKind::Variable(_) => {}
+        ExprKind::Call(_, args) | ExprKind::RegionCall(_, _, args) => {
             for arg in args {
                 run_on_expr_and_nested(arg, f);
             }
         }
-        Expr::Array(items) => {
+        ExprKind::Array(items) => {
             for item in items {
                 run_on_expr_and_nested(item, f);
             }
         }
-        Expr::Binary(lhs, _, rhs) => {
+        ExprKind::Dict(entries) => {
+            for (key, value) in entries {
+                run_on_expr_and_nested(key, f);
+                run_on_expr_and_nested(value, f);
+            }
+        }
+        ExprKind::Binary(lhs, _, rhs) => {
             run_on_expr_and_nested(lhs, f);
             run_on_expr_and_nested(rhs, f);
         }
-        Expr::MethodCall(expr, _, args) => {
+        ExprKind::Unary(_, expr) => {
+            run_on_expr_and_nested(expr, f);
+        }
+        ExprKind::Index(value, index) => {
+            run_on_expr_and_nested(value, f);
+            run_on_expr_and_nested(index, f);
+        }
+        ExprKind::Field(value, _) => {
+            run_on_expr_and_nested(value, f);
+        }
+        ExprKind::MethodCall(expr, _, args) | ExprKind::CallValue(expr, args) => {
             run_on_expr_and_nested(expr, f);
             for arg in args {
                 run_on_expr_and_nested(arg, f);
             }
         }
+        ExprKind::KeywordArgument(_, value) => {
+            run_on_expr_and_nested(value, f);
+        }
+        ExprKind::Lambda(_)
+        | ExprKind::FunctionValue(_, _)
+        | ExprKind::Global(_)
+        | ExprKind::Import(_) => {}
     }
 }
 
 fn unreachable_code_elimination(body: &mut Vec<Statement>) {
     let mut new_body = vec![];
 
-    for statement in std::mem::take(body) {
-        let returns = statement_returns(&statement);
-        
+    for mut statement in std::mem::take(body) {
+        // remove unreachable code in nested blocks too
+        match &mut statement.kind {
+            StatementKind::If(_, statements)
+            | StatementKind::ForLoop(_, _, _, statements)
+            | StatementKind::ForIn(_, _, statements)
+            | StatementKind::While(_, statements) => unreachable_code_elimination(statements),
+            StatementKind::IfElse(_, if_statements, else_statements) => {
+                unreachable_code_elimination(if_statements);
+                unreachable_code_elimination(else_statements);
+            }
+            StatementKind::Try(statements, catches) => {
+                unreachable_code_elimination(statements);
+                for catch in catches {
+                    unreachable_code_elimination(&mut catch.body);
+                }
+            }
+            _ => {}
+        }
+
+        let diverges = statement_diverges(&statement);
+
         new_body.push(statement);
 
-        if returns {
+        if diverges {
             break;
         }
     }
@@ -637,19 +2402,184 @@ fn unreachable_code_elimination(body: &mut Vec<Statement>) {
     *body = new_body;
 }
 
+/// whether the statements after this one can never run, because this one
+/// always returns, breaks or continues
+fn statement_diverges(statement: &Statement) -> bool {
+    match &statement.kind {
+        StatementKind::Break | StatementKind::Continue => true,
+        StatementKind::If(cond, statements) if cond.kind == ExprKind::Bool(true) => {
+            statements.iter().any(statement_diverges)
+        }
+        StatementKind::IfElse(_, if_statements, else_statements) => {
+            if_statements.iter().any(statement_diverges)
+                && else_statements.iter().any(statement_diverges)
+        }
+        _ => statement_returns(statement),
+    }
+}
+
+/// whether there is a try block anywhere in these statements
+fn contains_try(body: &[Statement]) -> bool {
+    body.iter().any(|statement| match &statement.kind {
+        StatementKind::Try(_, _) => true,
+        StatementKind::If(_, statements)
+        | StatementKind::ForLoop(_, _, _, statements)
+        | StatementKind::ForIn(_, _, statements)
+        | StatementKind::While(_, statements) => contains_try(statements),
+        StatementKind::IfElse(_, if_statements, else_statements) => {
+            contains_try(if_statements) || contains_try(else_statements)
+        }
+        _ => false,
+    })
+}
+
+/// find the catches that can't work: ones for an exception that doesn't
+/// exist, and ones after a catch that catches everything
+fn bad_catches(body: &[Statement]) -> Vec<(String, Span)> {
+    let mut bad = vec![];
+
+    for statement in body {
+        match &statement.kind {
+            StatementKind::Try(statements, catches) => {
+                bad.extend(bad_catches(statements));
+                for (i, catch) in catches.iter().enumerate() {
+                    match &catch.exception {
+                        Some(name) if !EXCEPTIONS.contains(&name.as_str()) => {
+                            bad.push((format!("unknown exception `{name}`"), catch.span));
+                        }
+                        None if i + 1 < catches.len() => bad.push((
+                            format!(
+                                "`catch ({})` catches everything, so it has to be last",
+                                catch.var
+                            ),
+                            catch.span,
+                        )),
+                        _ => {}
+                    }
+                    bad.extend(bad_catches(&catch.body));
+                }
+            }
+            StatementKind::If(_, statements)
+            | StatementKind::ForLoop(_, _, _, statements)
+            | StatementKind::ForIn(_, _, statements)
+            | StatementKind::While(_, statements) => bad.extend(bad_catches(statements)),
+            StatementKind::IfElse(_, if_statements, else_statements) => {
+                bad.extend(bad_catches(if_statements));
+                bad.extend(bad_catches(else_statements));
+            }
+            _ => {}
+        }
+    }
+
+    bad
+}
+
+/// find the calls with keyword arguments they can't take. only python
+/// functions and methods take them, after all the positional arguments.
+fn bad_arguments(exprs: Vec<&Expr>) -> Vec<(String, Span)> {
+    let mut bad = vec![];
+
+    for expr in exprs {
+        let args = match &expr.kind {
+            ExprKind::MethodCall(_, _, args) | ExprKind::CallValue(_, args) => args,
+            ExprKind::Call(_, args) | ExprKind::RegionCall(_, _, args) => {
+                let keyword_args = args
+                    .iter()
+                    .filter(|arg| matches!(arg.kind, ExprKind::KeywordArgument(_, _)))
+                    .map(|arg| {
+                        (
+                            "only python functions take keyword arguments".to_string(),
+                            arg.span,
+                        )
+                    });
+                bad.extend(keyword_args);
+                continue;
+            }
+            _ => continue,
+        };
+
+        let mut keys = vec![];
+        for arg in args {
+            match &arg.kind {
+                ExprKind::KeywordArgument(key, _) if keys.contains(&key) => {
+                    bad.push((format!("keyword argument `{key}` repeated"), arg.span));
+                }
+                ExprKind::KeywordArgument(key, _) => keys.push(key),
+                _ if !keys.is_empty() => {
+                    bad.push((
+                        "positional argument follows keyword argument".to_string(),
+                        arg.span,
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    bad
+}
+
+/// find all the `break` and `continue` statements that aren't inside a loop
+fn loop_jumps_outside_loop(body: &[Statement]) -> Vec<(&str, Span)> {
+    let mut jumps = vec![];
+
+    for statement in body {
+        match &statement.kind {
+            StatementKind::Break => jumps.push(("break", statement.span)),
+            StatementKind::Continue => jumps.push(("continue", statement.span)),
+            StatementKind::If(_, statements) => jumps.extend(loop_jumps_outside_loop(statements)),
+            StatementKind::IfElse(_, if_statements, else_statements) => {
+                jumps.extend(loop_jumps_outside_loop(if_statements));
+                jumps.extend(loop_jumps_outside_loop(else_statements));
+            }
+            StatementKind::Try(statements, catches) => {
+                jumps.extend(loop_jumps_outside_loop(statements));
+                for catch in catches {
+                    jumps.extend(loop_jumps_outside_loop(&catch.body));
+                }
+            }
+            // anything inside a loop is fine
+            _ => {}
+        }
+    }
+
+    jumps
+}
+
+/// whether this statement always returns. else-if chains are nested IfElse
+/// statements, so a chain only returns if every branch does, including a
+/// final `else`.
 fn statement_returns(statement: &Statement) -> bool {
-    match statement {
-        Statement::Noop => false,
-        Statement::Let(_, _) => false,
-        Statement::Return(_) => true,
-        Statement::Expression(_) => false,
-        Statement::Call(_, _) => false,
-        Statement::If(_, _) => false,
-        Statement::IfElse(_, if_statements, else_statements) => {
+    match &statement.kind {
+        StatementKind::Noop => false,
+        StatementKind::Let(_, _, _) => false,
+        StatementKind::Return(_) => true,
+        StatementKind::Expression(_) => false,
+        StatementKind::Call(_, _) => false,
+        StatementKind::If(cond, statements) => {
+            cond.kind == ExprKind::Bool(true) && statements.iter().any(statement_returns)
+        }
+        StatementKind::IfElse(_, if_statements, else_statements) => {
             if_statements.iter().any(statement_returns)
                 && else_statements.iter().any(statement_returns)
         }
-        Statement::ForLoop(_, _, _, statements) => false,
-        Statement::Assignment(_, _) => false,
+        StatementKind::ForLoop(_, _, _, _) => false,
+        StatementKind::ForIn(_, _, _) => false,
+        StatementKind::While(_, _) => false,
+        StatementKind::Break => false,
+        StatementKind::Continue => false,
+        StatementKind::Assignment(_, _) => false,
+        StatementKind::GlobalAssignment(_, _) => false,
+        StatementKind::IndexAssignment(_, _, _) => false,
+        StatementKind::FieldAssignment(_, _, _) => false,
+        // an exception that isn't caught is raised again, so only the
+        // catches that run matter
+        StatementKind::Try(statements, catches) => {
+            statements.iter().any(statement_returns)
+                && catches
+                    .iter()
+                    .all(|catch| catch.body.iter().any(statement_returns))
+        }
+        StatementKind::Raise(_) => true,
     }
-}
\ No newline at end of file
+}
@@ -1,8 +1,13 @@
 use std::collections::HashMap;
 
 use crate::grammar_ast::*;
+use crate::span::Span;
 
-pub fn gen_code(region: Region) -> String {
+/// generate the code for all the regions in a file. a single region is the
+/// python module, several regions are submodules of a `package` module.
+/// imported regions are only there to be called, so they don't get a python
+/// module.
+pub fn gen_code(regions: Vec<Region>, imported: Vec<Region>, package: &str) -> String {
     let mut ctx = CodegenCtx::default();
 
     // add Buffer builtin type
@@ -21,22 +26,112 @@ pub fn gen_code(region: Region) -> String {
     ctx.builtin_fns.push("free".to_string());
     ctx.builtin_fns.push("blackbox".to_string());
 
-    let code = region.gen_code(&mut ctx);
+    ctx.python_visible = true;
+
+    let code = match regions.as_slice() {
+        [region] => {
+            ctx.root_region = Some(region.name.clone());
+            region.gen_code(&mut ctx)
+        }
+        regions => {
+            let submodules = regions
+                .iter()
+                .map(|region| {
+                    let code = region.gen_code(&mut ctx);
+                    format!(
+                        "pub mod {} {{\nuse super::*;\n\n{code}}}",
+                        rust_ident(&region.name)
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n\n");
+
+            let submodule_registrations = regions
+                .iter()
+                .map(|region| {
+                    let name = rust_ident(&region.name);
+                    format!("m.add_wrapped(pyo3::wrap_pymodule!({name}::{name}))?;")
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            format!(
+                "#[pymodule]
+                {python_name}fn {name}(m: &Bound<'_, PyModule>) -> PyResult<()> {{
+                    {submodule_registrations}
+
+                    Ok(())
+                }}
+
+                {submodules}",
+                python_name = python_name_attr(package),
+                name = rust_ident(package)
+            )
+        }
+    };
+
+    // imported regions are modules next to the ones python can see
+    ctx.python_visible = false;
+    let helpers = imported
+        .iter()
+        .map(|region| {
+            let code = region.gen_code(&mut ctx);
+            format!(
+                "\n\npub mod {} {{\nuse super::*;\n\n{code}}}",
+                rust_ident(&region.name)
+            )
+        })
+        .collect::<String>();
 
     // add template header and body
-    format!("{HEADER}\n\n{code}\n")
+    format!("{HEADER}\n\n{code}{helpers}\n")
 }
 
 #[derive(Debug, Clone, Default)]
 struct CodegenCtx {
-    /// list of function parameter names in the current function
-    fn_params: Vec<String>,
+    /// variables in the current function that are always a PyAny: the
+    /// parameters without a type, and the variables of loops over python
+    /// values
+    any_vars: Vec<String>,
     /// list of functions that don't require the `py` parameter
     no_py_functions: Vec<String>,
     /// list of builtin fns, that are defined in the hardcoded footer code
     builtin_fns: Vec<String>,
     /// Buffer => free/borrow/etc
     builtin_types: HashMap<String, Vec<String>>,
+    /// for each loop we're in, the code that has to run before `continue`.
+    /// for loops run their update statement, while loops don't run anything.
+    loop_continue_code: Vec<String>,
+    /// if we're in a try block, how many loops we were in when it started.
+    /// the block runs in a closure, so returning from the function or
+    /// jumping out of those loops has to be done after it.
+    try_loop_depth: Option<usize>,
+    /// variables in the current function that are always a Buffer, so
+    /// indexing them doesn't have to go through python
+    buffer_vars: Vec<String>,
+    /// variables in the current function that are always a dict
+    dict_vars: Vec<String>,
+    /// the annotated types of the parameters and variables in the current
+    /// function. values assigned to them are converted to that type.
+    var_types: HashMap<String, Type>,
+    /// the annotated return type of the current function
+    return_type: Option<Type>,
+    /// name of the region we're generating code for
+    region_name: String,
+    /// the region that isn't in a module of its own, if the file only has
+    /// one. all the other regions are modules inside it.
+    root_region: Option<String>,
+    /// false for imported regions, which don't get a python module
+    python_visible: bool,
+    /// the classes in the current region, by name
+    classes: HashMap<String, Class>,
+    /// the class whose methods we're generating code for, if any
+    class_name: Option<String>,
+    /// variables in the current function that are always an object of one of
+    /// the region's classes, so its fields and methods can be used directly
+    class_vars: HashMap<String, String>,
+    /// the annotated types of the region's globals
+    global_types: HashMap<String, Type>,
 }
 
 trait CodeGen {
@@ -45,33 +140,73 @@ trait CodeGen {
 
 impl CodeGen for Region {
     fn gen_code(&self, ctx: &mut CodegenCtx) -> String {
-        // separate region into functions and statements
-        let (mut functions, statements) =
-            self.body.iter().partition::<Vec<_>, _>(|item| match item {
+        ctx.region_name = self.name.clone();
+
+        // separate region into classes, functions and statements
+        let classes = self
+            .body
+            .iter()
+            .filter_map(|item| match item {
+                RegionItem::Class(class) => Some(class),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let globals = self
+            .body
+            .iter()
+            .filter_map(|item| match item {
+                RegionItem::Global(global) => Some(global),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let (mut functions, statements) = self
+            .body
+            .iter()
+            .filter(|item| !matches!(item, RegionItem::Class(_) | RegionItem::Global(_)))
+            .partition::<Vec<_>, _>(|item| match item {
                 RegionItem::Function(_) => true,
                 RegionItem::Statement(_) => false,
+                RegionItem::Class(_) | RegionItem::Global(_) => unreachable!(),
             });
+        ctx.classes = classes
+            .iter()
+            .map(|class| (class.name.clone(), (*class).clone()))
+            .collect();
 
         // if we have statements, put them in a dummy function
         let func;
         if !statements.is_empty() {
             let f = Function {
-                name: "<Identifier, main>".to_string(),
+                name: "main".to_string(),
                 params: vec![],
+                return_type: None,
                 body: statements
                     .iter()
                     .map(|item| match item {
-                        RegionItem::Function(_) => unreachable!(),
                         RegionItem::Statement(stmt) => stmt.clone(),
+                        _ => unreachable!(),
                     })
                     .collect(),
+                span: self.span,
+                doc: None,
             };
 
             func = RegionItem::Function(f);
             functions.push(&func);
         }
 
-        // render functions
+        ctx.global_types = globals
+            .iter()
+            .filter_map(|global| Some((global.name.clone(), global.var_type.clone()?)))
+            .collect();
+        let globals_str = gen_imports(&self.python_imports) + &gen_globals(&globals, ctx);
+
+        // render classes and functions
+        let classes_str = classes
+            .iter()
+            .map(|class| class.gen_code(ctx))
+            .collect::<Vec<String>>()
+            .join("\n");
         let functions_str = functions
             .iter()
             .map(|item| item.gen_code(ctx))
@@ -82,26 +217,47 @@ impl CodeGen for Region {
         let functions_registrations = functions
             .iter()
             .map(|item| match item {
-                RegionItem::Function(func) => func.name.clone(),
-                RegionItem::Statement(_) => unreachable!(),
+                RegionItem::Function(func) => rust_ident(&func.name),
+                _ => unreachable!(),
             })
-            .map(|name| format!("m.add_function(wrap_pyfunction!({}, m)?)?;", id(name)))
+            .map(|name| format!("m.add_function(wrap_pyfunction!({}, m)?)?;", name))
             .collect::<Vec<String>>()
             .join("\n");
+        let classes_registrations = classes
+            .iter()
+            .map(|class| format!("m.add_class::<{}>()?;", rust_ident(&class.name)))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        // python can see the globals as attributes of the module
+        let globals_registration = if globals.is_empty() {
+            ""
+        } else {
+            "__GLOBALS.init(m)?;"
+        };
+
+        if !ctx.python_visible {
+            return format!("{globals_str}{classes_str}\n{functions_str}");
+        }
 
         format!(
-            "#[pymodule]
-        fn {name}(m: &Bound<'_, PyModule>) -> PyResult<()> {{
+            "{doc}#[pymodule]
+        {python_name}pub fn {name}(m: &Bound<'_, PyModule>) -> PyResult<()> {{
             m.add_class::<Buffer>()?;
+            {classes_registrations}
 
             {functions_registrations}
+            {globals_registration}
 
             Ok(())
         }}
         
+        {globals_str}{classes_str}
         {functions_str}
         ",
-            name = id(&self.name)
+            doc = doc_comment(&self.doc),
+            python_name = python_name_attr(&self.name),
+            name = rust_ident(&self.name)
         )
     }
 }
@@ -111,30 +267,155 @@ impl CodeGen for RegionItem {
         match self {
             RegionItem::Function(func) => func.gen_code(ctx),
             RegionItem::Statement(statement) => statement.gen_code(ctx),
+            RegionItem::Class(class) => class.gen_code(ctx),
+            RegionItem::Global(_) => unreachable!("globals are generated with their region"),
         }
     }
 }
 
+impl CodeGen for Class {
+    fn gen_code(&self, ctx: &mut CodegenCtx) -> String {
+        let name = rust_ident(&self.name);
+
+        // python can get and set every field
+        let fields_str = self
+            .fields
+            .iter()
+            .map(|field| {
+                let python_name = if is_reserved_name(&field.name) {
+                    format!(", name = {}", rust_string_literal(&field.name))
+                } else {
+                    String::new()
+                };
+                format!(
+                    "#[pyo3(get, set{python_name})]\npub {}: {},",
+                    rust_ident(&field.name),
+                    param_type(field)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        // the constructor takes the fields in order
+        let params_str = self
+            .fields
+            .iter()
+            .map(|field| format!("{}: {}", rust_ident(&field.name), param_type(field)))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let item_checks_str = item_checks(&self.fields);
+        let py = if item_checks_str.is_empty() {
+            "_py"
+        } else {
+            "py"
+        };
+        let field_names = self
+            .fields
+            .iter()
+            .map(|field| rust_ident(&field.name))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        ctx.class_name = Some(self.name.clone());
+        let methods_str = self
+            .methods
+            .iter()
+            .map(|method| method.gen_code(ctx))
+            .collect::<Vec<String>>()
+            .join("\n");
+        ctx.class_name = None;
+
+        let pyclass = if is_reserved_name(&self.name) {
+            format!("#[pyclass(name = {})]", rust_string_literal(&self.name))
+        } else {
+            "#[pyclass]".to_string()
+        };
+
+        format!(
+            "{doc}{pyclass}
+            pub struct {name} {{
+                {fields_str}
+            }}
+
+            #[pymethods]
+            impl {name} {{
+                #[new]
+                pub fn __new__({py}: Python<'_>, {params_str}) -> PyResult<Self> {{
+                    {item_checks_str}
+                    Ok(Self {{ {field_names} }})
+                }}
+
+                {methods_str}
+            }}
+
+            impl_var_for_class!({name});
+            ",
+            doc = doc_comment(&self.doc),
+        )
+    }
+}
+
 impl CodeGen for Function {
     fn gen_code(&self, ctx: &mut CodegenCtx) -> String {
         let param_names = self
             .params
             .iter()
-            .map(|param| id(&param.name))
+            .map(|param| param.name.clone())
             .collect::<Vec<String>>();
-        ctx.fn_params = param_names.clone();
+        ctx.any_vars = self
+            .params
+            .iter()
+            .filter(|param| param.var_type.is_none())
+            .map(|param| param.name.clone())
+            .collect();
+        ctx.buffer_vars = buffer_variables(&self.body);
+        ctx.dict_vars = dict_variables(&self.body);
+        ctx.var_types = annotated_types(self);
+        ctx.return_type = self.return_type.clone();
+        ctx.class_vars = class_variables(&self.body, &ctx.classes);
+        ctx.class_vars.retain(|var, _| !param_names.contains(var));
 
-        let params_str = param_names
-            .into_iter()
-            .map(|name| name + ": Py<PyAny>")
+        // a method is called on a `Py<Self>`, which has to be its first
+        // parameter. the rest are like a function's.
+        let (receiver_str, params) = match &ctx.class_name {
+            Some(class_name) => {
+                ctx.class_vars
+                    .insert("self".to_string(), class_name.clone());
+                (
+                    format!("{}: Py<Self>, ", rust_ident("self")),
+                    &self.params[1..],
+                )
+            }
+            None => (String::new(), &self.params[..]),
+        };
+
+        // pyo3 checks the types of annotated parameters, anything else is a
+        // PyAny
+        let params_str = params
+            .iter()
+            .map(|param| {
+                // parameters can be assigned to like any other variable
+                format!("mut {}: {}", rust_ident(&param.name), param_type(param))
+            })
             .collect::<Vec<String>>()
             .join(", ");
 
+        let item_checks_str = item_checks(params);
+
+        let return_type_str = match &self.return_type {
+            Some(return_type) => rust_type(return_type),
+            None => "Py<PyAny>".to_string(),
+        };
+
         let mut body = self.body.clone();
         // add return None if there's no return statement
-        match body.last() {
-            Some(Statement::Return(_)) => {}
-            _ => body.push(Statement::Return(Box::new(Expr::Uninitialized))),
+        match body.last().map(|stmt| &stmt.kind) {
+            Some(StatementKind::Return(_)) => {}
+            _ => {
+                let none = Expr::new(ExprKind::Uninitialized, self.span);
+                let return_none = StatementKind::Return(Box::new(none));
+                body.push(Statement::new(return_none, self.span));
+            }
         }
 
         let body_str = body
@@ -145,37 +426,171 @@ impl CodeGen for Function {
 
         format!(
             "
-            #[pyfunction]
-fn {name}(py: Python<'_>, {params_str}) -> Py<PyAny> {{
+            {doc}{pyfunction}{python_name}pub fn {name}({receiver_str}py: Python<'_>, {params_str}) -> PyResult<{return_type_str}> {{
+    {item_checks_str}
     {body_str}
 }}",
-            name = id(&self.name)
+            doc = doc_comment(&self.doc),
+            // methods are already in a #[pymethods] block
+            pyfunction = if ctx.class_name.is_some() {
+                ""
+            } else {
+                "#[pyfunction]\n"
+            },
+            python_name = python_name_attr(&self.name),
+            name = rust_ident(&self.name)
+        )
+    }
+}
+
+impl CodeGen for Lambda {
+    fn gen_code(&self, ctx: &mut CodegenCtx) -> String {
+        let function = &self.function;
+        let outer_ctx = ctx.clone();
+
+        // the lambda's body is like a function's, except that the captured
+        // variables keep the types they have outside of it
+        let captured = |var: &String| self.captures.contains(var);
+        ctx.any_vars.retain(captured);
+        ctx.any_vars.extend(
+            function
+                .params
+                .iter()
+                .filter(|param| param.var_type.is_none())
+                .map(|param| param.name.clone()),
+        );
+        ctx.buffer_vars.retain(captured);
+        ctx.buffer_vars.extend(buffer_variables(&function.body));
+        ctx.dict_vars.retain(captured);
+        ctx.dict_vars.extend(dict_variables(&function.body));
+        ctx.var_types.retain(|var, _| captured(var));
+        ctx.var_types.extend(annotated_types(function));
+        ctx.class_vars.retain(|var, _| captured(var));
+        ctx.class_vars
+            .extend(class_variables(&function.body, &ctx.classes));
+        for param in &function.params {
+            ctx.class_vars.remove(&param.name);
+        }
+        ctx.return_type = None;
+        ctx.loop_continue_code = vec![];
+        ctx.try_loop_depth = None;
+
+        let mut body = function.body.clone();
+        if !matches!(
+            body.last().map(|stmt| &stmt.kind),
+            Some(StatementKind::Return(_))
+        ) {
+            let none = Expr::new(ExprKind::Uninitialized, function.span);
+            let return_none = StatementKind::Return(Box::new(none));
+            body.push(Statement::new(return_none, function.span));
+        }
+        let body_str = body
+            .iter()
+            .map(|stmt| stmt.gen_code(ctx))
+            .collect::<Vec<String>>()
+            .join("\n");
+        *ctx = outer_ctx;
+
+        // the captured variables are copied when the lambda is made, and
+        // again for each call, so that a call can't change them for the next
+        let copy = |mutable: &str| {
+            self.captures
+                .iter()
+                .map(|var| format!("let {mutable}{0} = {0}.clone_ref(py);", rust_ident(var)))
+                .collect::<String>()
+        };
+        let params_str = function
+            .params
+            .iter()
+            .map(|param| format!("mut {}", rust_ident(&param.name)))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let conversions_str = function
+            .params
+            .iter()
+            .filter_map(|param| {
+                let var_type = param.var_type.as_ref()?;
+                Some(format!(
+                    "let mut {0}: {1} = convert(py, (&{0}))?;",
+                    rust_ident(&param.name),
+                    rust_type(var_type)
+                ))
+            })
+            .collect::<String>();
+
+        format!(
+            "{{
+                {captures_str}
+                lambda(py, move |__args, __kwargs| {{
+                    let py = __args.py();
+                    {copies_str}
+                    let [{params_str}] = lambda_args::<{count}>(__args, __kwargs)?;
+                    {conversions_str}
+                    {item_checks_str}
+                    {body_str}
+                }})?
+            }}",
+            captures_str = copy(""),
+            copies_str = copy("mut "),
+            count = function.params.len(),
+            item_checks_str = item_checks(&function.params),
         )
     }
 }
 
 impl CodeGen for Statement {
     fn gen_code(&self, ctx: &mut CodegenCtx) -> String {
-        match self {
-            Statement::Noop => "".to_string(),
-            Statement::Let(name, expr) => {
+        match &self.kind {
+            StatementKind::Noop => "".to_string(),
+            StatementKind::Let(name, None, expr) => {
+                let expr_str = expr.gen_code(ctx);
+                format!("let mut {} = {};", rust_ident(name), expr_str)
+            }
+            StatementKind::Let(name, Some(var_type), expr) => {
+                // an annotated variable without a value is left uninitialised,
+                // rust checks it's assigned before it's used
+                if expr.kind == ExprKind::Uninitialized {
+                    return format!("let mut {}: {};", rust_ident(name), rust_type(var_type));
+                }
+
                 let expr_str = expr.gen_code(ctx);
-                format!("let mut {} = {};", id(name), expr_str)
+                format!(
+                    "let mut {}: {} = convert(py, (&{}))?;",
+                    rust_ident(name),
+                    rust_type(var_type),
+                    expr_str
+                )
             }
-            Statement::Return(expr) => {
+            StatementKind::Return(expr) if ctx.try_loop_depth.is_some() => {
                 let expr_str = expr.gen_code(ctx);
-                format!("return ({}).to_pyany(py);", expr_str)
+                match ctx.return_type {
+                    Some(_) => format!("return Ok(Flow::Return(convert(py, (&{expr_str}))?));"),
+                    None => format!("return Ok(Flow::Return(({expr_str}).to_pyany(py)));"),
+                }
             }
-            Statement::Expression(expr) => {
+            StatementKind::Return(expr) => {
+                let expr_str = expr.gen_code(ctx);
+                match ctx.return_type {
+                    Some(_) => format!("return convert(py, (&{}));", expr_str),
+                    None => format!("return Ok(({}).to_pyany(py));", expr_str),
+                }
+            }
+            StatementKind::Expression(expr) => {
                 let expr_str = expr.gen_code(ctx);
                 format!("{};", expr_str)
             }
-            Statement::Call(name, args) => {
-                let args_str = format_args(id(name), args, ctx);
-
-                format!("{}({});", id(name), args_str)
+            StatementKind::Call(name, args) => format!("{};", gen_call(name, args, ctx)),
+            // the branch of an `if true` always runs, but it's still a block
+            // because of the variables declared in it
+            StatementKind::If(cond, body) if cond.kind == ExprKind::Bool(true) => {
+                let body_str = body
+                    .iter()
+                    .map(|stmt| stmt.gen_code(ctx))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                format!("{{\n{}}}", body_str)
             }
-            Statement::If(cond, body) => {
+            StatementKind::If(cond, body) => {
                 let cond_str = cond.gen_code(ctx);
                 let body_str = body
                     .iter()
@@ -183,51 +598,289 @@ impl CodeGen for Statement {
                     .collect::<Vec<String>>()
                     .join("\n");
                 format!(
-                    "if ({}).to_pyany(py).is_truthy(py).unwrap()
+                    "if ({}).truthy(py)?
                  {{\n{}}}",
                     cond_str, body_str
                 )
             }
-            Statement::IfElse(cond, if_body, else_body) => {
+            StatementKind::IfElse(cond, if_body, else_body) => {
                 let cond_str = cond.gen_code(ctx);
                 let if_body_str = if_body
                     .iter()
                     .map(|stmt| stmt.gen_code(ctx))
                     .collect::<Vec<String>>()
                     .join("\n");
-                let else_body_str = else_body
-                    .iter()
-                    .map(|stmt| stmt.gen_code(ctx))
-                    .collect::<Vec<String>>()
-                    .join("\n");
+                let else_body_str = match else_body.as_slice() {
+                    // an else-if chain is an if statement nested in the else
+                    // body, so emit it as a rust else-if chain too
+                    [stmt @ Statement {
+                        kind: StatementKind::If(..) | StatementKind::IfElse(..),
+                        ..
+                    }] => stmt.gen_code(ctx),
+
+                    _ => {
+                        let body_str = else_body
+                            .iter()
+                            .map(|stmt| stmt.gen_code(ctx))
+                            .collect::<Vec<String>>()
+                            .join("\n");
+                        format!("{{\n{}}}", body_str)
+                    }
+                };
                 format!(
-                    "if {}.to_pyany(py).is_truthy(py).unwrap()
-                     {{\n{}}} else {{\n{}}}",
+                    "if ({}).truthy(py)?
+                     {{\n{}}} else {}",
                     cond_str, if_body_str, else_body_str
                 )
             }
-            Statement::ForLoop(init, cond, update, body) => {
+            StatementKind::ForLoop(init, cond, update, body) => {
                 let init_str = init.gen_code(ctx);
                 let cond_str = cond.gen_code(ctx);
                 let update_str = update.gen_code(ctx);
+
+                ctx.loop_continue_code.push(update_str.clone());
                 let body_str = body
                     .iter()
                     .map(|stmt| stmt.gen_code(ctx))
                     .collect::<Vec<String>>()
                     .join("\n");
+                ctx.loop_continue_code.pop();
 
                 format!(
                     "{init_str}
-                    while {cond_str} {{
+                    while ({cond_str}).truthy(py)? {{
                         {body_str}
                         {update_str}
                     }}
                     "
                 )
             }
-            Statement::Assignment(name, expr) => {
+            StatementKind::ForIn(var, iter, body) => {
+                let var_str = rust_ident(var);
+
+                // ranges and buffers loop over ints in rust, anything else
+                // is iterated by python and the variable is a PyAny
+                let (loop_str, first_str, var_type) = match &iter.kind {
+                    ExprKind::Call(name, args) if name == "range" => (
+                        format!("for mut {var_str} in {}", gen_range(args, ctx)),
+                        String::new(),
+                        Some(Type::Int),
+                    ),
+                    _ if is_buffer(iter, ctx) => (
+                        format!("for mut {var_str} in {}.bytes()", iter.gen_code(ctx)),
+                        String::new(),
+                        Some(Type::Int),
+                    ),
+                    _ => (
+                        format!(
+                            "for {var_str} in ({}).to_pyany(py).bind(py).try_iter()?",
+                            iter.gen_code(ctx)
+                        ),
+                        format!("let mut {var_str} = {var_str}?.unbind();"),
+                        None,
+                    ),
+                };
+
+                // the loop variable only has that type inside the loop
+                let outer_types = (ctx.var_types.clone(), ctx.any_vars.clone());
+                ctx.var_types.remove(var);
+                match var_type {
+                    Some(var_type) => {
+                        ctx.var_types.insert(var.clone(), var_type);
+                    }
+                    None => ctx.any_vars.push(var.clone()),
+                }
+
+                ctx.loop_continue_code.push(String::new());
+                let body_str = body
+                    .iter()
+                    .map(|stmt| stmt.gen_code(ctx))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                ctx.loop_continue_code.pop();
+                (ctx.var_types, ctx.any_vars) = outer_types;
+
+                format!(
+                    "{loop_str} {{
+                        {first_str}
+                        {body_str}
+                    }}
+                    "
+                )
+            }
+            StatementKind::While(cond, body) => {
+                let cond_str = cond.gen_code(ctx);
+
+                ctx.loop_continue_code.push(String::new());
+                let body_str = body
+                    .iter()
+                    .map(|stmt| stmt.gen_code(ctx))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                ctx.loop_continue_code.pop();
+
+                format!(
+                    "while ({cond_str}).truthy(py)? {{
+                        {body_str}
+                    }}
+                    "
+                )
+            }
+            // jumping out of a loop the try block is in
+            StatementKind::Break if ctx.try_loop_depth == Some(ctx.loop_continue_code.len()) => {
+                "return Ok(Flow::Break);".to_string()
+            }
+            StatementKind::Continue if ctx.try_loop_depth == Some(ctx.loop_continue_code.len()) => {
+                "return Ok(Flow::Continue);".to_string()
+            }
+            StatementKind::Break => "break;".to_string(),
+            StatementKind::Continue => {
+                // `continue` in a for loop still runs the update statement
+                let continue_code = ctx.loop_continue_code.last().cloned().unwrap_or_default();
+                format!("{{ {continue_code} continue; }}")
+            }
+            StatementKind::Assignment(name, expr) => {
                 let expr_str = expr.gen_code(ctx);
-                format!("{} = {};", id(name), expr_str)
+                match ctx.var_types.get(name) {
+                    Some(_) => format!("{} = convert(py, (&{}))?;", rust_ident(name), expr_str),
+                    None if ctx.any_vars.contains(name) => {
+                        format!("{} = ({}).to_pyany(py);", rust_ident(name), expr_str)
+                    }
+                    None => format!("{} = {};", rust_ident(name), expr_str),
+                }
+            }
+            StatementKind::GlobalAssignment(name, expr) => {
+                let name_str = rust_string_literal(name);
+                let expr_str = expr.gen_code(ctx);
+                match ctx.global_types.get(name) {
+                    Some(var_type) => format!(
+                        "{{ let value: {} = convert(py, (&{expr_str}))?; __GLOBALS.set(py, {name_str}, &value)?; }}",
+                        rust_type(var_type)
+                    ),
+                    None => format!("__GLOBALS.set(py, {name_str}, (&{expr_str}))?;"),
+                }
+            }
+            StatementKind::IndexAssignment(value, index, expr) => {
+                let value_str = value.gen_code(ctx);
+                let index_str = index.gen_code(ctx);
+                let expr_str = expr.gen_code(ctx);
+
+                if is_buffer(value, ctx) {
+                    format!("{value_str}.set_item(py, (&{index_str}), (&{expr_str}))?;")
+                } else {
+                    format!("set_item(py, (&{value_str}), (&{index_str}), (&{expr_str}))?;")
+                }
+            }
+            StatementKind::FieldAssignment(value, field, expr) => {
+                let value_str = value.gen_code(ctx);
+                let expr_str = expr.gen_code(ctx);
+
+                // fields of our own classes are set directly, anything else
+                // uses python's setattr. the value is worked out before the
+                // object is borrowed, because it could use the object too.
+                let field_value = match known_field(value, field, ctx) {
+                    Some(Variable {
+                        var_type: Some(_), ..
+                    }) => format!("convert(py, (&{expr_str}))?"),
+                    Some(_) => format!("({expr_str}).to_pyany(py)"),
+                    None => {
+                        return format!(
+                            "setattr(py, (&{value_str}), {}, (&{expr_str}))?;",
+                            rust_string_literal(field)
+                        )
+                    }
+                };
+                format!(
+                    "{{ let __field_value = {field_value}; {value_str}.borrow_mut(py).{} = __field_value; }}",
+                    rust_ident(field)
+                )
+            }
+            StatementKind::Try(body, catches) => {
+                // the block runs in a closure, so that `?` stops it with the
+                // error instead of returning from the function
+                let outer_try = ctx.try_loop_depth.replace(ctx.loop_continue_code.len());
+                let body_str = body
+                    .iter()
+                    .map(|stmt| stmt.gen_code(ctx))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                ctx.try_loop_depth = outer_try;
+
+                let return_type = ctx
+                    .return_type
+                    .as_ref()
+                    .map_or("Py<PyAny>".to_string(), rust_type);
+                let return_str = match ctx.try_loop_depth {
+                    Some(_) => "return Ok(Flow::Return(__value));",
+                    None => "return Ok(__value);",
+                };
+                let jumps_str = if ctx.loop_continue_code.is_empty() {
+                    "Ok(Flow::Break | Flow::Continue) => unreachable!(),".to_string()
+                } else {
+                    format!(
+                        "Ok(Flow::Break) => {{ {} }} Ok(Flow::Continue) => {{ {} }}",
+                        Statement::new(StatementKind::Break, self.span).gen_code(ctx),
+                        Statement::new(StatementKind::Continue, self.span).gen_code(ctx)
+                    )
+                };
+
+                // the first catch for the exception runs. if there isn't one,
+                // the exception is raised again.
+                let mut catches_str = catches
+                    .iter()
+                    .map(|catch| {
+                        let outer_types = (ctx.var_types.clone(), ctx.any_vars.clone());
+                        ctx.var_types.remove(&catch.var);
+                        ctx.any_vars.push(catch.var.clone());
+                        let body_str = catch
+                            .body
+                            .iter()
+                            .map(|stmt| stmt.gen_code(ctx))
+                            .collect::<Vec<String>>()
+                            .join("\n");
+                        (ctx.var_types, ctx.any_vars) = outer_types;
+
+                        let block = format!(
+                            "{{
+                                let mut {} = __err.value(py).clone().into_any().unbind();
+                                {body_str}
+                            }}",
+                            rust_ident(&catch.var)
+                        );
+                        match &catch.exception {
+                            Some(name) => format!(
+                                "if __err.is_instance(py, exception(py, {})?.bind(py)) {block}",
+                                rust_string_literal(name)
+                            ),
+                            None => block,
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" else ");
+                if catches
+                    .last()
+                    .is_some_and(|catch| catch.exception.is_some())
+                {
+                    catches_str.push_str(" else { return Err(__err); }");
+                }
+
+                format!(
+                    "let __try = (|| -> PyResult<Flow<{return_type}>> {{
+                        {body_str}
+                        Ok(Flow::Next)
+                    }})();
+                    match __try {{
+                        Ok(Flow::Next) => {{}}
+                        Ok(Flow::Return(__value)) => {{ {return_str} }}
+                        {jumps_str}
+                        Err(__err) => {catches_str}
+                    }}
+                    "
+                )
+            }
+            StatementKind::Raise(expr) => {
+                let expr_str = expr.gen_code(ctx);
+                format!("return Err(raise(py, (&{expr_str})));")
             }
         }
     }
@@ -235,53 +888,209 @@ impl CodeGen for Statement {
 
 impl CodeGen for Expr {
     fn gen_code(&self, ctx: &mut CodegenCtx) -> String {
-        match self {
-            Expr::Uninitialized => "py.None()".to_string(),
-            Expr::Number(n) => n.to_string(),
-            Expr::StringLiteral(s) => format!("\"{}\"", s),
-            Expr::Variable(v) => id(v),
-            Expr::Call(name, args) => {
-                let args_str = format_args(id(name), args, ctx);
-
-                format!("{}({})", id(name), args_str)
-            }
-            Expr::Array(elements) => {
-                // only support empty arrays for now
-                if !elements.is_empty() {
-                    panic!("Array elements not supported yet");
+        match &self.kind {
+            ExprKind::Uninitialized => "py.None()".to_string(),
+            // numbers are wrapped in a Num so that they have the same type as
+            // the result of arithmetic, which could be an int or a float
+            ExprKind::Number(n) => format!("Num::Int({n})"),
+            ExprKind::Float(Float(f)) => format!("Num::Float({f:?})"),
+            ExprKind::Bool(b) => b.to_string(),
+            ExprKind::None => "py.None()".to_string(),
+            ExprKind::StringLiteral(s) => rust_string_literal(s),
+            ExprKind::Variable(v) => rust_ident(v),
+            ExprKind::Call(name, args) => gen_call(name, args, ctx),
+            ExprKind::RegionCall(region, name, args) => {
+                let path = region_function_path(region, name, ctx);
+                gen_user_call(&path, None, args, ctx)
+            }
+            // a function used as a value is its python function
+            ExprKind::FunctionValue(region, name) => format!(
+                "wrap_pyfunction!({}, py)?.into_any().unbind()",
+                region_function_path(region, name, ctx)
+            ),
+            ExprKind::Global(name) => format!("__GLOBALS.get(py, {})?", rust_string_literal(name)),
+            ExprKind::Import(name) => format!("{}.get(py)?", import_static(name)),
+            ExprKind::CallValue(callee, args) => {
+                let callee_str = callee.gen_code(ctx);
+                let (args_str, kwargs_str) = gen_python_args(args, ctx);
+                match kwargs_str {
+                    Some(kwargs_str) => format!(
+                        "({callee_str}).to_pyany(py).call(py, ({args_str}), Some(&{kwargs_str}))?"
+                    ),
+                    None => format!("({callee_str}).to_pyany(py).call1(py, ({args_str}))?"),
+                }
+            }
+            // keyword arguments are generated by the call they're in
+            ExprKind::KeywordArgument(_, _) => unreachable!(),
+            ExprKind::Lambda(lambda) => lambda.gen_code(ctx),
+            ExprKind::Array(elements) => {
+                if elements.is_empty() {
+                    return "PyList::new(py, Vec::<Buffer>::new())?.unbind()".to_string();
                 }
 
-                "PyList::new(py, Vec::<Buffer>::new()).unwrap().unbind()".to_string()
+                // if all the elements have the same type, the list is made
+                // from a Vec of that type. otherwise they all become PyAny.
+                let types = elements
+                    .iter()
+                    .map(|element| element_type(element, ctx))
+                    .collect::<Vec<_>>();
+                let same_type = types[0].is_some() && types.iter().all(|t| *t == types[0]);
+
+                let elements_str = elements
+                    .iter()
+                    .map(|element| match &element.kind {
+                        _ if !same_type => format!("({}).to_pyany(py)", element.gen_code(ctx)),
+                        ExprKind::Number(n) => format!("{n}i64"),
+                        ExprKind::Float(Float(f)) => format!("{f:?}f64"),
+                        _ => element.gen_code(ctx),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                format!("PyList::new(py, vec![{elements_str}])?.unbind()")
             }
-            Expr::Binary(lhs, op, rhs) => {
+            ExprKind::Dict(entries) => {
+                let entries_str = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "__dict.set_item(({}).to_pyany(py), ({}).to_pyany(py))?;",
+                            key.gen_code(ctx),
+                            value.gen_code(ctx)
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                format!("{{ let __dict = PyDict::new(py); {entries_str} __dict.unbind() }}")
+            }
+            ExprKind::Binary(lhs, op, rhs) => {
                 let lhs_str = lhs.gen_code(ctx);
                 let op_str = op.gen_code(ctx);
                 let rhs_str = rhs.gen_code(ctx);
 
-                format!(
-                    "({}).to_usize(py) {} ({}).to_usize(py)",
-                    lhs_str, op_str, rhs_str
-                )
+                match op {
+                    // logical operators work on any value, using python truthiness
+                    BinaryOp::And | BinaryOp::Or => format!(
+                        "(({}).truthy(py)? {} ({}).truthy(py)?)",
+                        lhs_str, op_str, rhs_str
+                    ),
+
+                    // equality works on any value, using python's ==
+                    BinaryOp::Equal | BinaryOp::NotEqual => format!(
+                        "(({}).to_pyany(py).bind(py).eq(({}).to_pyany(py))? {} true)",
+                        lhs_str, rhs_str, op_str
+                    ),
+
+                    // dividing by zero raises a ZeroDivisionError
+                    BinaryOp::Div => {
+                        format!("({lhs_str}).to_num(py)?.div(({rhs_str}).to_num(py)?)?")
+                    }
+                    BinaryOp::Mod => {
+                        format!("({lhs_str}).to_num(py)?.rem(({rhs_str}).to_num(py)?)?")
+                    }
+
+                    // everything else is arithmetic on ints or floats
+                    _ => format!(
+                        "(({}).to_num(py)? {} ({}).to_num(py)?)",
+                        lhs_str, op_str, rhs_str
+                    ),
+                }
+            }
+            ExprKind::Unary(op, expr) => {
+                let expr_str = expr.gen_code(ctx);
+
+                match op {
+                    UnaryOp::Neg => format!("(-({}).to_num(py)?)", expr_str),
+                    UnaryOp::Not => {
+                        format!("(!({}).truthy(py)?)", expr_str)
+                    }
+                }
             }
-            Expr::MethodCall(obj, method_name, args) => {
-                let method_name = id(method_name);
-                let mut obj_str = obj.gen_code(ctx);
-                let args_str = format_args(method_name.clone(), args, ctx);
+            ExprKind::Index(value, index) => {
+                let value_str = value.gen_code(ctx);
+                let index_str = index.gen_code(ctx);
+
+                // buffers can be indexed directly, anything else uses python's
+                // __getitem__. both raise an IndexError when out of range.
+                if is_buffer(value, ctx) {
+                    format!("{value_str}.get_item(py, (&{index_str}))?")
+                } else {
+                    format!("get_item(py, (&{value_str}), (&{index_str}))?")
+                }
+            }
+            ExprKind::Field(value, field) => {
+                let value_str = value.gen_code(ctx);
+
+                // fields of our own classes are read directly, and copied out
+                // so the object isn't borrowed for longer than it has to be
+                let clone = match known_field(value, field, ctx).map(|f| &f.var_type) {
+                    Some(Some(Type::Int | Type::Float | Type::Bool)) => "",
+                    Some(Some(Type::Str | Type::Buffer)) => ".clone()",
+                    Some(Some(Type::List(_) | Type::Dict(_, _)) | None) => ".clone_ref(py)",
+                    None => {
+                        return format!(
+                            "getattr(py, (&{value_str}), {})?",
+                            rust_string_literal(field)
+                        )
+                    }
+                };
+                format!("{value_str}.borrow(py).{}{clone}", rust_ident(field))
+            }
+            ExprKind::MethodCall(obj, method_name, args) => {
+                let obj_str = obj.gen_code(ctx);
+
+                // only python methods take keyword arguments
+                let has_kwargs = args
+                    .iter()
+                    .any(|arg| matches!(arg.kind, ExprKind::KeywordArgument(_, _)));
+                if has_kwargs {
+                    let (args_str, kwargs_str) = gen_python_args(args, ctx);
+                    return format!(
+                        "({obj_str}).to_pyany(py).call_method(py, {}, ({args_str}), Some(&{}))?",
+                        rust_string_literal(method_name),
+                        kwargs_str.unwrap()
+                    );
+                }
+
+                // methods of our own classes are called directly
+                let class_name = class_of(obj, ctx)
+                    .filter(|class| class.methods.iter().any(|m| m.name == *method_name))
+                    .map(|class| class.name.clone());
+                if let Some(class_name) = class_name {
+                    let path = format!("{}::{}", rust_ident(&class_name), rust_ident(method_name));
+                    let receiver = format!("{obj_str}.clone_ref(py)");
+                    return gen_user_call(&path, Some(&receiver), args, ctx);
+                }
+
+                // the keys, values or items of a dict are a list, so they can
+                // be indexed and iterated over
+                let is_dict_view = ["keys", "values", "items"].contains(&method_name.as_str());
+                if is_dict_view && args.is_empty() && is_dict(obj, ctx) {
+                    return format!("({obj_str}).to_pydict(py)?.bind(py).{method_name}().unbind()");
+                }
 
                 // determine whether this method is one on a builtin rust class e.g. Buffer
                 let is_builtin = ctx
                     .builtin_types
                     .iter()
-                    .any(|(_, methods)| methods.contains(&method_name));
+                    .any(|(_, methods)| methods.contains(method_name));
 
                 if is_builtin {
-                    format!("({}).to_buffer(py).{}({})", obj_str, method_name, args_str)
-                } else {
-                    // assume it's a python class
+                    let args_str = format_args(method_name, args, ctx);
                     format!(
-                        "{}.call_method(py, \"{}\", ({},), None).unwrap()",
+                        "({}).to_buffer(py)?.{}({})?",
                         obj_str, method_name, args_str
                     )
+                } else {
+                    // assume it's a python class, which takes python values
+                    let (args_str, _) = gen_python_args(args, ctx);
+                    format!(
+                        "{}.call_method1(py, {}, ({}))?",
+                        obj_str,
+                        rust_string_literal(method_name),
+                        args_str
+                    )
                 }
             }
         }
@@ -292,25 +1101,141 @@ impl CodeGen for BinaryOp {
     fn gen_code(&self, _ctx: &mut CodegenCtx) -> String {
         match self {
             BinaryOp::Add => "+".to_string(),
+            BinaryOp::Sub => "-".to_string(),
             BinaryOp::Mult => "*".to_string(),
+            BinaryOp::Div => "/".to_string(),
+            BinaryOp::Mod => "%".to_string(),
+            BinaryOp::Equal => "==".to_string(),
+            BinaryOp::NotEqual => "!=".to_string(),
             BinaryOp::LessThan => "<".to_string(),
+            BinaryOp::GreaterThan => ">".to_string(),
+            BinaryOp::LessThanOrEqual => "<=".to_string(),
+            BinaryOp::GreaterThanOrEqual => ">=".to_string(),
+            BinaryOp::And => "&&".to_string(),
+            BinaryOp::Or => "||".to_string(),
         }
     }
 }
 
-/// utility function to extract identifier name from <Identifier, name>
-fn id(v: impl AsRef<str>) -> String {
-    // <Identifier, name> => "name"
+/// utility function to turn a `///` doc comment from the source code into a
+/// rust doc comment. pyo3 then uses it as the python docstring.
+fn doc_comment(doc: &Option<String>) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
 
-    // get "name>"
-    let name_rangle = v.as_ref().split(' ').nth(1).unwrap();
+    doc.lines()
+        .map(|line| format!("/// {line}").trim_end().to_string() + "\n")
+        .collect()
+}
+
+/// utility function to turn a string into a rust string literal, escaping any
+/// quotes, backslashes and special characters
+fn rust_string_literal(s: &str) -> String {
+    format!("\"{}\"", s.escape_default())
+}
 
-    // get "name";
-    name_rangle[0..name_rangle.len() - 1].to_string()
+/// utility function to turn an identifier from the source code into a rust
+/// identifier. identifiers that are rust keywords are escaped, e.g. `type`
+/// becomes `r#type`, and ones that the generated code uses itself are moved
+/// to the `__user_` prefix, which it never uses, e.g. `py` becomes
+/// `__user_py`.
+fn rust_ident(name: &str) -> String {
+    if is_reserved_name(name) {
+        format!("__user_{name}")
+    } else if NON_RAW_KEYWORDS.contains(&name) {
+        format!("__{name}")
+    } else if RUST_KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
 }
 
+/// utility function to keep the python name of a function or module whose
+/// rust name had to be changed by rust_ident(). pyo3 already removes the `r#`
+/// from raw identifiers.
+fn python_name_attr(name: &str) -> String {
+    if NON_RAW_KEYWORDS.contains(&name) || is_reserved_name(name) {
+        format!("#[pyo3(name = \"{name}\")]\n")
+    } else {
+        String::new()
+    }
+}
+
+/// utility function to check whether a name from the source code would clash
+/// with one the generated code uses. its own helpers start with `__`, except
+/// the temp variables of the optimizations, which never clash with a name
+/// from the source code.
+fn is_reserved_name(name: &str) -> bool {
+    (name.starts_with("__") && !name.starts_with("__temp_"))
+        || name.starts_with("Py")
+        || RESERVED_NAMES.contains(&name)
+}
+
+/// rust's strict and reserved keywords, which have to be raw identifiers when
+/// used as names in the generated code
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// keywords that can't be raw identifiers either, so they are renamed instead
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super", "_"];
+
+/// the names that the generated code uses without a `__` or `Py` prefix: its
+/// own variables, and the names it gets from rust, pyo3 and util.rs
+const RESERVED_NAMES: &[&str] = &[
+    "py",
+    "_py",
+    "m",
+    "Ok",
+    "Err",
+    "Some",
+    "None",
+    "Option",
+    "Result",
+    "Vec",
+    "String",
+    "Box",
+    "i64",
+    "f64",
+    "bool",
+    "usize",
+    "isize",
+    "u8",
+    "str",
+    "Bound",
+    "allocate",
+    "free",
+    "blackbox",
+    "convert",
+    "check_list_items",
+    "check_dict_items",
+    "CloneRef",
+    "range",
+    "exception",
+    "raise",
+    "Flow",
+    "lambda",
+    "lambda_args",
+    "Globals",
+    "kwargs",
+    "get_item",
+    "set_item",
+    "getattr",
+    "setattr",
+    "Buffer",
+    "Var",
+    "cant_convert",
+    "Num",
+];
+
 /// utility function to format function arguments when calling a function
-fn format_args(fn_name: String, args: &[Box<Expr>], ctx: &mut CodegenCtx) -> String {
+fn format_args(fn_name: &str, args: &[Expr], ctx: &mut CodegenCtx) -> String {
     let mut args = args
         .iter()
         .map(|arg| arg.gen_code(ctx))
@@ -320,15 +1245,465 @@ fn format_args(fn_name: String, args: &[Box<Expr>], ctx: &mut CodegenCtx) -> Str
         *arg = format!("(&{arg})");
     }
 
-    if !ctx.no_py_functions.contains(&fn_name) {
+    if !ctx.no_py_functions.iter().any(|f| f == fn_name) {
         args.insert(0, "py".to_string());
     }
 
     args.join(", ")
 }
 
+/// utility function to generate a call to a builtin function, or to a
+/// function from the source code
+fn gen_call(fn_name: &str, args: &[Expr], ctx: &mut CodegenCtx) -> String {
+    if ctx.builtin_fns.iter().any(|f| f == fn_name) {
+        let args_str = format_args(fn_name, args, ctx);
+        return format!("{fn_name}({args_str})?");
+    }
+
+    // outside of a for loop, a range is a list
+    if fn_name == "range" {
+        return format!(
+            "PyList::new(py, {}.collect::<Vec<i64>>())?.unbind()",
+            gen_range(args, ctx)
+        );
+    }
+
+    // python exceptions are made by python
+    if EXCEPTIONS.contains(&fn_name) {
+        let args_str = args
+            .iter()
+            .map(|arg| format!("({}).to_pyany(py),", arg.gen_code(ctx)))
+            .collect::<String>();
+        return format!(
+            "exception(py, {})?.call1(py, ({args_str}))?",
+            rust_string_literal(fn_name)
+        );
+    }
+
+    // calling a class makes a new object
+    if ctx.classes.contains_key(fn_name) {
+        let path = format!("{}::__new__", rust_ident(fn_name));
+        let new = gen_user_call(&path, None, args, ctx);
+        return format!("Py::new(py, {new})?");
+    }
+
+    gen_user_call(&rust_ident(fn_name), None, args, ctx)
+}
+
+/// utility function to generate a call to a function or method from the
+/// source code. the arguments are converted to the types of its parameters,
+/// and errors are passed on with `?`.
+fn gen_user_call(
+    path: &str,
+    receiver: Option<&str>,
+    args: &[Expr],
+    ctx: &mut CodegenCtx,
+) -> String {
+    let mut args = args
+        .iter()
+        .map(|arg| format!("convert(py, (&{}))?", arg.gen_code(ctx)))
+        .collect::<Vec<String>>();
+    args.insert(0, "py".to_string());
+    if let Some(receiver) = receiver {
+        args.insert(0, receiver.to_string());
+    }
+
+    format!("{path}({})?", args.join(", "))
+}
+
+/// utility function to generate an iterator over the ints of `range(...)`.
+/// ints that are known when compiling use a rust range, anything else is
+/// checked at runtime like python does.
+fn gen_range(args: &[Expr], ctx: &mut CodegenCtx) -> String {
+    let zero = Expr::new(ExprKind::Number(0), Span::default());
+    let one = Expr::new(ExprKind::Number(1), Span::default());
+    let (start, stop, step) = match args {
+        [stop] => (&zero, stop, &one),
+        [start, stop] => (start, stop, &one),
+        [start, stop, step] => (start, stop, step),
+        _ => unreachable!("`range` arguments are checked in clean_ast"),
+    };
+
+    let known_int = |expr: &Expr, ctx: &CodegenCtx| match &expr.kind {
+        ExprKind::Number(n) => Some(format!("{n}i64")),
+        ExprKind::Variable(name) if ctx.var_types.get(name) == Some(&Type::Int) => {
+            Some(rust_ident(name))
+        }
+        _ => None,
+    };
+    if let (Some(start_str), Some(stop_str), ExprKind::Number(step)) =
+        (known_int(start, ctx), known_int(stop, ctx), &step.kind)
+    {
+        if *step == 1 {
+            return format!("({start_str}..{stop_str})");
+        }
+        if *step > 0 {
+            return format!("({start_str}..{stop_str}).step_by({step})");
+        }
+    }
+
+    format!(
+        "range(py, (&{}), (&{}), (&{}))?",
+        start.gen_code(ctx),
+        stop.gen_code(ctx),
+        step.gen_code(ctx)
+    )
+}
+
+/// utility function to generate the globals of a region, which are kept in a
+/// static. their values are set in order, like the statements of a function.
+fn gen_globals(globals: &[&Global], ctx: &mut CodegenCtx) -> String {
+    if globals.is_empty() {
+        return String::new();
+    }
+
+    ctx.any_vars.clear();
+    ctx.buffer_vars.clear();
+    ctx.dict_vars.clear();
+    ctx.var_types.clear();
+    ctx.class_vars.clear();
+    ctx.return_type = None;
+
+    let values_str = globals
+        .iter()
+        .map(|global| {
+            let name = rust_string_literal(&global.name);
+            let value_str = global.value.gen_code(ctx);
+            match &global.var_type {
+                Some(var_type) => format!(
+                    "let value: {} = convert(py, (&{value_str}))?;\nm.setattr({name}, value.to_pyany(py))?;",
+                    rust_type(var_type)
+                ),
+                None => format!("m.setattr({name}, ({value_str}).to_pyany(py))?;"),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!(
+        "static __GLOBALS: Globals = Globals::new({region}, __init_globals);
+
+        fn __init_globals(m: &Bound<'_, PyModule>) -> PyResult<()> {{
+            let py = m.py();
+            {values_str}
+            Ok(())
+        }}
+
+        ",
+        region = rust_string_literal(&ctx.region_name),
+    )
+}
+
+/// utility function to generate the python modules and attributes a region
+/// imports. each one is imported the first time it's used.
+fn gen_imports(imports: &[PythonImport]) -> String {
+    imports
+        .iter()
+        .map(|import| {
+            let attribute = match &import.attribute {
+                Some(attribute) => format!("Some({})", rust_string_literal(attribute)),
+                None => "None".to_string(),
+            };
+            format!(
+                "#[allow(non_upper_case_globals)]
+                static {}: PyImport = PyImport::new({}, {attribute});
+
+                ",
+                import_static(import.name()),
+                rust_string_literal(&import.module)
+            )
+        })
+        .collect()
+}
+
+/// utility function to name the static that holds something a region
+/// imported from python
+fn import_static(name: &str) -> String {
+    format!("__import_{name}")
+}
+
+/// utility function to generate the arguments of a call to python, as a
+/// tuple of the positional arguments and a dict of the keyword arguments, if
+/// there are any
+fn gen_python_args(args: &[Expr], ctx: &mut CodegenCtx) -> (String, Option<String>) {
+    let mut args_str = String::new();
+    let mut kwargs = vec![];
+    for arg in args {
+        match &arg.kind {
+            ExprKind::KeywordArgument(key, value) => kwargs.push(format!(
+                "({}, ({}).to_pyany(py))",
+                rust_string_literal(key),
+                value.gen_code(ctx)
+            )),
+            _ => args_str += &format!("({}).to_pyany(py),", arg.gen_code(ctx)),
+        }
+    }
+
+    if kwargs.is_empty() {
+        return (args_str, None);
+    }
+    (
+        args_str,
+        Some(format!("kwargs(py, [{}])?", kwargs.join(", "))),
+    )
+}
+
+/// utility function to find the rust path of a function in a region. regions
+/// are sibling rust modules, unless one of them is the root that the others
+/// are inside.
+fn region_function_path(region: &str, name: &str, ctx: &CodegenCtx) -> String {
+    if region == ctx.region_name {
+        rust_ident(name)
+    } else if ctx.root_region.as_deref() == Some(&ctx.region_name) {
+        format!("{}::{}", rust_ident(region), rust_ident(name))
+    } else if ctx.root_region.as_deref() == Some(region) {
+        format!("super::{}", rust_ident(name))
+    } else {
+        format!("super::{}::{}", rust_ident(region), rust_ident(name))
+    }
+}
+
+/// utility function to check if an expression is known to be a Buffer
+fn is_buffer(expr: &Expr, ctx: &CodegenCtx) -> bool {
+    match &expr.kind {
+        ExprKind::Variable(name) => {
+            ctx.buffer_vars.contains(name) || ctx.var_types.get(name) == Some(&Type::Buffer)
+        }
+        ExprKind::Call(name, _) => name == "allocate",
+        _ => false,
+    }
+}
+
+/// utility function to check if an expression is known to be a dict
+fn is_dict(expr: &Expr, ctx: &CodegenCtx) -> bool {
+    match &expr.kind {
+        ExprKind::Variable(name) => {
+            ctx.dict_vars.contains(name)
+                || matches!(ctx.var_types.get(name), Some(Type::Dict(_, _)))
+        }
+        ExprKind::Dict(_) => true,
+        _ => false,
+    }
+}
+
+/// utility function to get the class of an expression, if it's known to be an
+/// object of one of the region's classes
+fn class_of<'a>(expr: &Expr, ctx: &'a CodegenCtx) -> Option<&'a Class> {
+    match &expr.kind {
+        ExprKind::Variable(name) => ctx.classes.get(ctx.class_vars.get(name)?),
+        ExprKind::Call(name, _) => ctx.classes.get(name),
+        _ => None,
+    }
+}
+
+/// utility function to get a field of a value, if the value is known to be an
+/// object of one of the region's classes
+fn known_field<'a>(value: &Expr, field: &str, ctx: &'a CodegenCtx) -> Option<&'a Variable> {
+    class_of(value, ctx)?
+        .fields
+        .iter()
+        .find(|f| f.name == field)
+}
+
+/// the rust type of an array element, if it's known at compile time
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ElementType {
+    Int,
+    Float,
+    Str,
+    Buffer,
+    List,
+    Dict,
+}
+
+/// utility function to get the type of an array element, if it's known
+fn element_type(expr: &Expr, ctx: &CodegenCtx) -> Option<ElementType> {
+    match &expr.kind {
+        ExprKind::Number(_) => Some(ElementType::Int),
+        ExprKind::Float(_) => Some(ElementType::Float),
+        ExprKind::StringLiteral(_) => Some(ElementType::Str),
+        ExprKind::Array(_) => Some(ElementType::List),
+        ExprKind::Dict(_) => Some(ElementType::Dict),
+        _ if is_buffer(expr, ctx) => Some(ElementType::Buffer),
+        _ => None,
+    }
+}
+
+/// utility function to get the rust type of a parameter or field. pyo3 checks
+/// the types of annotated ones, anything else is a PyAny.
+fn param_type(param: &Variable) -> String {
+    match &param.var_type {
+        Some(var_type) => rust_type(var_type),
+        None => "Py<PyAny>".to_string(),
+    }
+}
+
+/// utility function to check the items of `list<T>` and `dict<K, V>`
+/// parameters or fields. pyo3 only checks that a list is a list.
+fn item_checks(params: &[Variable]) -> String {
+    params
+        .iter()
+        .filter_map(|param| match &param.var_type {
+            Some(Type::List(item_type)) => Some(format!(
+                "check_list_items::<{}>(py, &{})?;",
+                rust_type(item_type),
+                rust_ident(&param.name)
+            )),
+            Some(Type::Dict(key_type, value_type)) => Some(format!(
+                "check_dict_items::<{}, {}>(py, &{})?;",
+                rust_type(key_type),
+                rust_type(value_type),
+                rust_ident(&param.name)
+            )),
+            _ => None,
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// utility function to get the rust type for a type annotation
+fn rust_type(var_type: &Type) -> String {
+    match var_type {
+        Type::Int => "i64".to_string(),
+        Type::Float => "f64".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::Str => "String".to_string(),
+        Type::Buffer => "Buffer".to_string(),
+        Type::List(_) => "Py<PyList>".to_string(),
+        Type::Dict(_, _) => "Py<PyDict>".to_string(),
+    }
+}
+
+/// utility function to find the annotated types of a function's parameters
+/// and variables
+fn annotated_types(function: &Function) -> HashMap<String, Type> {
+    fn lets(body: &[Statement], types: &mut HashMap<String, Type>) {
+        for statement in body {
+            match &statement.kind {
+                StatementKind::Let(name, Some(var_type), _) => {
+                    types.insert(name.clone(), var_type.clone());
+                }
+                StatementKind::If(_, body)
+                | StatementKind::ForIn(_, _, body)
+                | StatementKind::While(_, body) => lets(body, types),
+                StatementKind::IfElse(_, if_body, else_body) => {
+                    lets(if_body, types);
+                    lets(else_body, types);
+                }
+                StatementKind::ForLoop(init, _, _, body) => {
+                    lets(std::slice::from_ref(init), types);
+                    lets(body, types);
+                }
+                StatementKind::Try(body, catches) => {
+                    lets(body, types);
+                    for catch in catches {
+                        lets(&catch.body, types);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut types = function
+        .params
+        .iter()
+        .filter_map(|param| Some((param.name.clone(), param.var_type.clone()?)))
+        .collect::<HashMap<_, _>>();
+    lets(&function.body, &mut types);
+
+    types
+}
+
+/// utility function to find the variables in a function that are only ever
+/// set to a newly allocated Buffer
+fn buffer_variables(body: &[Statement]) -> Vec<String> {
+    variables_only_set_to(
+        body,
+        |expr| matches!(&expr.kind, ExprKind::Call(name, _) if name == "allocate"),
+    )
+}
+
+/// utility function to find the variables in a function that are only ever
+/// set to a dict literal
+fn dict_variables(body: &[Statement]) -> Vec<String> {
+    variables_only_set_to(body, |expr| matches!(expr.kind, ExprKind::Dict(_)))
+}
+
+/// utility function to find the variables in a function that are only ever
+/// set to a new object of one of the classes, and which class that is
+fn class_variables(
+    body: &[Statement],
+    classes: &HashMap<String, Class>,
+) -> HashMap<String, String> {
+    classes
+        .keys()
+        .flat_map(|class| {
+            variables_only_set_to(
+                body,
+                |expr| matches!(&expr.kind, ExprKind::Call(name, _) if name == class),
+            )
+            .into_iter()
+            .map(|var| (var, class.clone()))
+        })
+        .collect()
+}
+
+/// utility function to find the variables in a function whose every `let`
+/// and assignment is a value that `is_value` accepts. loop and catch
+/// variables are set by the loop or catch, so they're never accepted.
+fn variables_only_set_to(body: &[Statement], is_value: impl Fn(&Expr) -> bool) -> Vec<String> {
+    fn assignments<'a>(body: &'a [Statement], found: &mut Vec<(&'a str, Option<&'a Expr>)>) {
+        for statement in body {
+            match &statement.kind {
+                StatementKind::Let(name, _, expr) | StatementKind::Assignment(name, expr) => {
+                    found.push((name, Some(expr)));
+                }
+                StatementKind::If(_, body) | StatementKind::While(_, body) => {
+                    assignments(body, found);
+                }
+                StatementKind::ForIn(var, _, body) => {
+                    found.push((var, None));
+                    assignments(body, found);
+                }
+                StatementKind::Try(body, catches) => {
+                    assignments(body, found);
+                    for catch in catches {
+                        found.push((&catch.var, None));
+                        assignments(&catch.body, found);
+                    }
+                }
+                StatementKind::IfElse(_, if_body, else_body) => {
+                    assignments(if_body, found);
+                    assignments(else_body, found);
+                }
+                StatementKind::ForLoop(init, _, update, body) => {
+                    assignments(std::slice::from_ref(init), found);
+                    assignments(std::slice::from_ref(update), found);
+                    assignments(body, found);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = vec![];
+    assignments(body, &mut found);
+
+    found
+        .iter()
+        .filter(|(name, _)| {
+            found
+                .iter()
+                .filter(|(other, _)| other == name)
+                .all(|(_, expr)| expr.is_some_and(&is_value))
+        })
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
 /// the hard-coded bit of code at the top and bottom of the generated code
-const HEADER: &str = "use pyo3::types::PyList;
+const HEADER: &str = "use pyo3::types::{PyDict, PyList};
 use pyo3::{prelude::*};
 
 use crate::util::*;";
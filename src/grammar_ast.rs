@@ -1,64 +1,326 @@
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::span::Span;
+
+/// A source file: the files it imports, then its regions.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub imports: Vec<Import>,
+    pub regions: Vec<Region>,
+}
+
+/// `import "path/to/file.txt";`
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: String, // Relative to the importing file
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
 pub struct Region {
     pub name: String,
+    pub python_imports: Vec<PythonImport>, // From `import module;`
+    pub uses: Vec<Use>,                    // From `use Region.function;`
     pub body: Vec<RegionItem>,
+    pub span: Span,
+    pub doc: Option<String>, // From `///` doc comments
+}
+
+/// `use Region.function;`, which lets the function be called without the
+/// region name.
+#[derive(Debug, Clone)]
+pub struct Use {
+    pub region: String,
+    pub function: String,
+    pub span: Span,
+}
+
+/// `import module;` or `from module import name;`, which imports a python
+/// module or something from one. the region's functions can use it by name.
+#[derive(Debug, Clone)]
+pub struct PythonImport {
+    pub module: String,            // Can have dots after `from`, e.g. `os.path`
+    pub attribute: Option<String>, // From `from module import attribute;`
+    pub span: Span,
+}
+
+impl PythonImport {
+    /// the name the import is used with
+    pub fn name(&self) -> &str {
+        self.attribute.as_deref().unwrap_or(&self.module)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum RegionItem {
     Function(Function),
     Statement(Statement),
+    Class(Class),
+    Global(Global),
+}
+
+/// `const NAME = value;`, or a region-level `let` that the region's functions
+/// use. globals are attributes of the region's python module, which are set
+/// when it's imported. constants are inlined where they're used.
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub name: String,
+    pub var_type: Option<Type>, // From `name: type`, if there is one
+    pub value: Box<Expr>,
+    pub constant: bool,
+    pub span: Span,
+}
+
+/// `class Name { field; function method(self) {...} }`, which becomes a python
+/// class. its constructor takes the fields in order.
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: String,
+    pub fields: Vec<Variable>,
+    pub methods: Vec<Function>, // The first parameter is always `self`
+    pub span: Span,
+    pub doc: Option<String>, // From `///` doc comments
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
-    pub params: Vec<Variable>, // Function parameters
-    pub body: Vec<Statement>,  // Function body consisting of statements
+    pub params: Vec<Variable>,     // Function parameters
+    pub return_type: Option<Type>, // From `-> type`, if there is one
+    pub body: Vec<Statement>,      // Function body consisting of statements
+    pub span: Span,
+    pub doc: Option<String>, // From `///` doc comments
 }
 
 #[derive(Debug, Clone)]
 pub struct Variable {
     pub name: String,
-    pub var_type: Type, // Variable type (Int, String, etc.)
+    pub var_type: Option<Type>, // From `name: type`, if there is one
 }
 
 #[derive(Debug, Clone)]
-pub enum Statement {
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
     Noop,
-    Let(String, Box<Expr>),
+    Let(String, Option<Type>, Box<Expr>),
     Return(Box<Expr>),
     Expression(Box<Expr>),
-    Call(String, Vec<Box<Expr>>),
+    Call(String, Vec<Expr>),
     If(Box<Expr>, Vec<Statement>),
     IfElse(Box<Expr>, Vec<Statement>, Vec<Statement>), // Added
     ForLoop(Box<Statement>, Box<Expr>, Box<Statement>, Vec<Statement>),
+    ForIn(String, Box<Expr>, Vec<Statement>), // for var in expr { ... }
+    While(Box<Expr>, Vec<Statement>),
+    Break,
+    Continue,
     Assignment(String, Box<Expr>),
+    GlobalAssignment(String, Box<Expr>), // global = expr, for one of the region's globals
+    IndexAssignment(Box<Expr>, Box<Expr>, Box<Expr>), // value[index] = expr
+    FieldAssignment(Box<Expr>, String, Box<Expr>), // value.field = expr
+    Try(Vec<Statement>, Vec<Catch>),
+    Raise(Box<Expr>),
+}
+
+/// `catch (var: Exception) { ... }`, or `catch (var) { ... }` to catch any
+/// exception
+#[derive(Debug, Clone)]
+pub struct Catch {
+    pub var: String,
+    pub exception: Option<String>,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+/// the python exceptions that can be raised and caught by name. `Error` is
+/// any exception, like python's `Exception`.
+pub const EXCEPTIONS: &[&str] = &[
+    "Error",
+    "BaseException",
+    "Exception",
+    "ArithmeticError",
+    "AssertionError",
+    "AttributeError",
+    "EOFError",
+    "FileNotFoundError",
+    "ImportError",
+    "IndexError",
+    "KeyError",
+    "LookupError",
+    "MemoryError",
+    "NameError",
+    "NotImplementedError",
+    "OSError",
+    "OverflowError",
+    "PermissionError",
+    "RecursionError",
+    "RuntimeError",
+    "StopIteration",
+    "TimeoutError",
+    "TypeError",
+    "UnicodeError",
+    "ValueError",
+    "ZeroDivisionError",
+];
+
+/// An expression and where it is in the source code.
+///
+/// Two expressions are equal if they do the same thing, regardless of where
+/// they are. This is what lets CSE and loop invariant motion find repeated
+/// expressions.
+#[derive(Debug, Clone, Eq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl Hash for Expr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Expr {
+pub enum ExprKind {
     Uninitialized, // Added
     Number(i32),
+    Float(Float),
+    Bool(bool),
+    None,
     StringLiteral(String),
     Variable(String),
-    Call(String, Vec<Box<Expr>>),
-    Array(Vec<Box<Expr>>),
+    Call(String, Vec<Expr>),
+    RegionCall(String, String, Vec<Expr>), // Region.function(args)
+    Array(Vec<Expr>),
+    Dict(Vec<(Expr, Expr)>), // { key: value, ... }
     Binary(Box<Expr>, BinaryOp, Box<Expr>),
-    MethodCall(Box<Expr>, String, Vec<Box<Expr>>),
+    Unary(UnaryOp, Box<Expr>),
+    Index(Box<Expr>, Box<Expr>), // value[index]
+    Field(Box<Expr>, String),    // value.field
+    MethodCall(Box<Expr>, String, Vec<Expr>),
+    Lambda(Lambda),                     // fn (params) { body }
+    CallValue(Box<Expr>, Vec<Expr>),    // calling a variable that holds a function
+    FunctionValue(String, String),      // a region's function used as a value
+    Global(String),                     // one of the region's globals
+    Import(String),                     // something the region imported from python
+    KeywordArgument(String, Box<Expr>), // key=value, in the arguments of a python call
+}
+
+/// A lambda, `fn (params) { body }`.
+///
+/// The variables it uses from the function it's in are found when cleaning
+/// the AST, and they're copied into the lambda when it's made. Every lambda
+/// in the source code makes a new function, so two lambdas are only the same
+/// expression if they're the same lambda. Spans don't say which file they're
+/// in, so each lambda gets an id of its own instead.
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub function: Box<Function>,
+    pub captures: Vec<String>,
+    id: usize,
+}
+
+impl Lambda {
+    pub fn new(function: Function) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        Lambda {
+            function: Box::new(function),
+            captures: Vec::new(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+impl PartialEq for Lambda {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Lambda {}
+
+impl Hash for Lambda {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+/// A float literal.
+///
+/// Rust floats aren't `Eq` or `Hash`, so literals are compared by their bits
+/// instead. That way `0.0` and `-0.0` are different expressions, and so are
+/// `1.0` and `1`.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum BinaryOp {
     Add,
+    Sub,
     Mult,
+    Div,
+    Mod,
+    Equal,
+    NotEqual,
     LessThan,
-    // Add other operators as needed
+    GreaterThan,
+    LessThanOrEqual,
+    GreaterThanOrEqual,
+    And,
+    Or,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg, // -x
+    Not, // !x
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
-    Int32,      // Integer type
-    StringType, // String type
-    Bool,       // Boolean type
+    Int,                        // int
+    Float,                      // float
+    Bool,                       // bool
+    Str,                        // str
+    Buffer,                     // buffer
+    List(Box<Type>),            // list<T>
+    Dict(Box<Type>, Box<Type>), // dict<K, V>
 }
@@ -0,0 +1,1103 @@
+use std::collections::HashMap;
+
+use crate::clean_ast::{clean_ast, region_functions, resolve_region_calls};
+use crate::codegen::gen_code;
+use crate::grammar::{ProgramParser, RegionParser};
+use crate::grammar_ast::{
+    Expr, ExprKind, Program, Region, RegionItem, Statement, StatementKind, Type,
+};
+use crate::span::{Position, Span};
+use crate::token_fsm::{LexError, Tokeniser};
+use crate::{Operator, SpannedToken, Token};
+
+/// The tokens in some code, without their spans.
+fn tokens(code: &str) -> Vec<Token> {
+    let (tokens, errors) = Tokeniser::tokenise(code);
+    assert_eq!(errors, vec![], "tokenising `{code}`");
+
+    tokens.into_iter().map(|t| t.token).collect()
+}
+
+/// The errors in some code, and where they are as `line:col` of their start.
+fn lex_errors(code: &str) -> Vec<String> {
+    let (_, errors) = Tokeniser::tokenise(code);
+    errors
+        .iter()
+        .map(|e| format!("{}: {}", e.span, e.message))
+        .collect()
+}
+
+/// A span from `(offset, line, col)` to `(offset, line, col)`.
+fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+    Span::new(
+        Position::new(start.0, start.1, start.2),
+        Position::new(end.0, end.1, end.2),
+    )
+}
+
+/// The doc comments for the parser, like main.rs gives them.
+fn doc_comments(tokens: &[SpannedToken]) -> HashMap<Position, String> {
+    tokens
+        .iter()
+        .filter(|t| !t.doc_comments.is_empty())
+        .map(|t| (t.span.start, t.doc_comments.join("\n")))
+        .collect()
+}
+
+fn parse_region(source: &str) -> Result<Region, String> {
+    let (tokens, errors) = Tokeniser::tokenise(source);
+    if let Some(error) = errors.first() {
+        return Err(error.message.clone());
+    }
+
+    let token_stream = tokens
+        .iter()
+        .map(|t| Ok((t.span.start, t.token.clone(), t.span.end)));
+
+    RegionParser::new()
+        .parse(&doc_comments(&tokens), token_stream)
+        .map_err(|e| format!("{e:?}"))
+}
+
+fn parse_program(source: &str) -> Result<Program, String> {
+    let (tokens, errors) = Tokeniser::tokenise(source);
+    if let Some(error) = errors.first() {
+        return Err(error.message.clone());
+    }
+
+    let token_stream = tokens
+        .iter()
+        .map(|t| Ok((t.span.start, t.token.clone(), t.span.end)));
+
+    ProgramParser::new()
+        .parse(&doc_comments(&tokens), token_stream)
+        .map_err(|e| format!("{e:?}"))
+}
+
+/// Parse a single expression, by wrapping it in a region as an expression
+/// statement.
+fn parse_expr(expr: &str) -> Result<Expr, String> {
+    let region = parse_region(&format!("region test {{ {expr}; }}"))?;
+
+    match region.body.as_slice() {
+        [RegionItem::Statement(statement)] => match &statement.kind {
+            StatementKind::Expression(expr) => Ok(*expr.clone()),
+            kind => panic!("expected an expression statement, got {kind:?}"),
+        },
+        body => panic!("expected a single statement, got {body:?}"),
+    }
+}
+
+/// Turn an expression into a fully parenthesised s-expression, e.g.
+/// `a + b * c` becomes `(Add a (Mult b c))`.
+fn sexpr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Number(n) => n.to_string(),
+        ExprKind::Float(f) => format!("{:?}", f.0),
+        ExprKind::Bool(b) => b.to_string(),
+        ExprKind::Variable(name) => name.clone(),
+        ExprKind::Binary(lhs, op, rhs) => format!("({op:?} {} {})", sexpr(lhs), sexpr(rhs)),
+        ExprKind::Unary(op, expr) => format!("({op:?} {})", sexpr(expr)),
+        ExprKind::Index(value, index) => format!("(Index {} {})", sexpr(value), sexpr(index)),
+        ExprKind::Field(value, field) => format!("(Field {} {field})", sexpr(value)),
+        ExprKind::Array(items) => {
+            let items = items.iter().map(|item| format!(" {}", sexpr(item)));
+            format!("(Array{})", items.collect::<String>())
+        }
+        ExprKind::Dict(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| format!(" ({} {})", sexpr(key), sexpr(value)));
+            format!("(Dict{})", entries.collect::<String>())
+        }
+        ExprKind::Call(name, args) => {
+            let args = args.iter().map(|arg| format!(" {}", sexpr(arg)));
+            format!("({name}{})", args.collect::<String>())
+        }
+        ExprKind::RegionCall(region, name, args) => {
+            let args = args.iter().map(|arg| format!(" {}", sexpr(arg)));
+            format!("({region}.{name}{})", args.collect::<String>())
+        }
+        ExprKind::MethodCall(obj, name, args) => {
+            let args = args.iter().map(|arg| format!(" {}", sexpr(arg)));
+            format!("(.{name} {}{})", sexpr(obj), args.collect::<String>())
+        }
+        ExprKind::CallValue(callee, args) => {
+            let args = args.iter().map(|arg| format!(" {}", sexpr(arg)));
+            format!("(call {}{})", sexpr(callee), args.collect::<String>())
+        }
+        ExprKind::Lambda(lambda) => format!("(fn {})", lambda.captures.join(" ")),
+        ExprKind::Global(name) => format!("(global {name})"),
+        ExprKind::Import(name) => format!("(import {name})"),
+        ExprKind::KeywordArgument(key, value) => format!("{key}={}", sexpr(value)),
+        kind => panic!("unexpected expression {kind:?}"),
+    }
+}
+
+fn assert_parses_as(expr: &str, expected: &str) {
+    match parse_expr(expr) {
+        Ok(parsed) => assert_eq!(sexpr(&parsed), expected, "parsing `{expr}`"),
+        Err(e) => panic!("failed to parse `{expr}`: {e}"),
+    }
+}
+
+/// Check what an expression is after the AST has been cleaned, i.e. after
+/// constant folding. The expression can use the variable `a`, which can hold
+/// anything, the int `i` and the float `x`.
+fn assert_cleans_as(expr: &str, expected: &str) {
+    let source = format!("region test {{ function test(a, i: int, x: float) {{ {expr}; }} }}");
+    let mut region = parse_region(&source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+    match &function.body[0].kind {
+        StatementKind::Expression(cleaned) => {
+            assert_eq!(sexpr(cleaned), expected, "cleaning `{expr}`")
+        }
+        kind => panic!("expected an expression statement, got {kind:?}"),
+    }
+}
+
+/// The values of the temp variables that loop invariant motion made in a
+/// function body, which can use the ints `a`, `b` and `n`, and `s`, which can
+/// hold anything. temp variables for a loop in an if or else body are
+/// declared in that body.
+fn hoisted(body: &str) -> Vec<String> {
+    fn temp_values(statements: &[Statement], values: &mut Vec<String>) {
+        for statement in statements {
+            match &statement.kind {
+                StatementKind::Let(name, _, value) if name.starts_with("__temp_") => {
+                    values.push(sexpr(value));
+                }
+                StatementKind::If(_, statements) => temp_values(statements, values),
+                StatementKind::IfElse(_, if_statements, else_statements) => {
+                    temp_values(if_statements, values);
+                    temp_values(else_statements, values);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let source = format!("region test {{ function test(a: int, b: int, n: int, s) {{ {body} }} }}");
+    let mut region = parse_region(&source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+    let mut values = vec![];
+    temp_values(&function.body, &mut values);
+    values.sort();
+    values
+}
+
+fn assert_rejected(expr: &str, message: &str) {
+    match parse_expr(expr) {
+        Ok(parsed) => panic!("`{expr}` should not parse, got {}", sexpr(&parsed)),
+        Err(e) => assert!(e.contains(message), "parsing `{expr}` gave: {e}"),
+    }
+}
+
+#[test]
+fn multiplicative_binds_tighter_than_additive() {
+    assert_parses_as("a + b * c", "(Add a (Mult b c))");
+    assert_parses_as("a * b + c", "(Add (Mult a b) c)");
+    assert_parses_as("a - b / c", "(Sub a (Div b c))");
+    assert_parses_as("a % b - c", "(Sub (Mod a b) c)");
+}
+
+#[test]
+fn additive_binds_tighter_than_comparison() {
+    assert_parses_as("a * b < c", "(LessThan (Mult a b) c)");
+    assert_parses_as("a < b * c", "(LessThan a (Mult b c))");
+    assert_parses_as("a + b >= c - d", "(GreaterThanOrEqual (Add a b) (Sub c d))");
+}
+
+#[test]
+fn comparison_binds_tighter_than_equality() {
+    assert_parses_as("a < b == c > d", "(Equal (LessThan a b) (GreaterThan c d))");
+    assert_parses_as("a != b <= c", "(NotEqual a (LessThanOrEqual b c))");
+}
+
+#[test]
+fn equality_binds_tighter_than_logical_and() {
+    assert_parses_as("a == b && c != d", "(And (Equal a b) (NotEqual c d))");
+}
+
+#[test]
+fn logical_and_binds_tighter_than_logical_or() {
+    assert_parses_as("a || b && c", "(Or a (And b c))");
+    assert_parses_as("a && b || c && d", "(Or (And a b) (And c d))");
+}
+
+#[test]
+fn unary_binds_tightest() {
+    assert_parses_as("-a * b", "(Mult (Neg a) b)");
+    assert_parses_as("!a == b", "(Equal (Not a) b)");
+    assert_parses_as("!a && !b", "(And (Not a) (Not b))");
+    assert_parses_as("!-a", "(Not (Neg a))");
+}
+
+#[test]
+fn arithmetic_and_logical_operators_are_left_associative() {
+    assert_parses_as("a - b - c", "(Sub (Sub a b) c)");
+    assert_parses_as("a / b / c", "(Div (Div a b) c)");
+    assert_parses_as("a / b % c", "(Mod (Div a b) c)");
+    assert_parses_as("a + b - c + d", "(Add (Sub (Add a b) c) d)");
+    assert_parses_as("a && b && c", "(And (And a b) c)");
+    assert_parses_as("a || b || c", "(Or (Or a b) c)");
+}
+
+#[test]
+fn parentheses_override_precedence() {
+    assert_parses_as("(a + b) * c", "(Mult (Add a b) c)");
+    assert_parses_as("a - (b - c)", "(Sub a (Sub b c))");
+    assert_parses_as("(a < b) < c", "(LessThan (LessThan a b) c)");
+    assert_parses_as("(a || b) && c", "(And (Or a b) c)");
+}
+
+#[test]
+fn indexing_binds_tighter_than_unary() {
+    assert_parses_as("-a[0]", "(Neg (Index a 0))");
+    assert_parses_as("a[i][j]", "(Index (Index a i) j)");
+    assert_parses_as("a[i + 1] * 2", "(Mult (Index a (Add i 1)) 2)");
+    assert_parses_as("f(a)[0]", "(Index (f a) 0)");
+}
+
+#[test]
+fn fields_chain_with_methods_and_indexing() {
+    assert_parses_as("-a.b", "(Neg (Field a b))");
+    assert_parses_as("a.b.c(1)[2].d", "(Field (Index (.c (Field a b) 1) 2) d)");
+}
+
+#[test]
+fn classes_have_fields_then_methods() {
+    let source = "
+        region test {
+            class Point {
+                x;
+                y: int;
+                function move_by(self, dx) { self.x = self.x + dx; }
+            }
+        }
+    ";
+    let region = parse_region(source).unwrap();
+
+    let [RegionItem::Class(class)] = region.body.as_slice() else {
+        panic!("expected a single class, got {:?}", region.body);
+    };
+    let fields = class
+        .fields
+        .iter()
+        .map(|f| (f.name.as_str(), f.var_type.clone()));
+    assert_eq!(
+        fields.collect::<Vec<_>>(),
+        [("x", None), ("y", Some(Type::Int))]
+    );
+
+    let [method] = class.methods.as_slice() else {
+        panic!("expected a single method, got {:?}", class.methods);
+    };
+    match &method.body[0].kind {
+        StatementKind::FieldAssignment(value, field, expr) => {
+            assert_eq!(
+                (sexpr(value), field.as_str(), sexpr(expr)),
+                (
+                    "self".to_string(),
+                    "x",
+                    "(Add (Field self x) dx)".to_string()
+                )
+            );
+        }
+        kind => panic!("expected a field assignment, got {kind:?}"),
+    }
+}
+
+#[test]
+fn chained_comparisons_are_rejected() {
+    assert_rejected("a < b < c", "comparison operators cannot be chained");
+    assert_rejected("a <= b >= c", "comparison operators cannot be chained");
+    assert_rejected("a < b + 1 > c", "comparison operators cannot be chained");
+}
+
+#[test]
+fn chained_equalities_are_rejected() {
+    assert_rejected("a == b == c", "equality operators cannot be chained");
+    assert_rejected("a == b != c", "equality operators cannot be chained");
+}
+
+#[test]
+fn minus_is_always_an_operator() {
+    let a = Token::Identifier("a".to_string());
+    let f = Token::Identifier("f".to_string());
+    let one = Token::Number("1".to_string());
+    let minus = Token::Operator(Operator::Minus);
+
+    for code in ["a-1", "a - 1", "a -1"] {
+        assert_eq!(tokens(code), [a.clone(), minus.clone(), one.clone()]);
+    }
+    assert_eq!(tokens("-a"), [minus.clone(), a.clone()]);
+    assert_eq!(
+        tokens("f(-1)"),
+        [f, Token::Lpar, minus.clone(), one.clone(), Token::Rpar]
+    );
+}
+
+#[test]
+fn strings_decode_their_escapes() {
+    assert_eq!(
+        tokens(r#""a\"b\\c\nd\x41\x7e""#),
+        [Token::StringLiteral("a\"b\\c\ndA~".to_string())]
+    );
+    assert_eq!(tokens(r#""""#), [Token::StringLiteral(String::new())]);
+}
+
+#[test]
+fn invalid_escapes_are_reported_at_their_string() {
+    let (tokens, errors) = Tokeniser::tokenise(r#"f("a\qb", x)"#);
+    assert_eq!(
+        errors,
+        [LexError {
+            message: "invalid escape sequence \\q".to_string(),
+            text: r#""a\qb""#.to_string(),
+            span: span((2, 1, 3), (8, 1, 9)),
+        }]
+    );
+    // the rest of the string isn't tokenised as code
+    let tokens = tokens.into_iter().map(|t| t.token).collect::<Vec<_>>();
+    let f = Token::Identifier("f".to_string());
+    let x = Token::Identifier("x".to_string());
+    assert_eq!(tokens, [f, Token::Lpar, Token::Comma, x, Token::Rpar]);
+
+    let (_, errors) = Tokeniser::tokenise(r#"s = "\x4g";"#);
+    assert_eq!(
+        errors,
+        [LexError {
+            message: "invalid escape sequence \\x4g".to_string(),
+            text: r#""\x4g""#.to_string(),
+            span: span((4, 1, 5), (10, 1, 11)),
+        }]
+    );
+}
+
+#[test]
+fn unterminated_strings_are_errors() {
+    let (_, errors) = Tokeniser::tokenise("s = \"abc;\nt = 1;");
+    assert_eq!(
+        errors,
+        [LexError {
+            message: "unterminated string literal".to_string(),
+            text: "\"abc;\nt = 1;".to_string(),
+            span: span((4, 1, 5), (16, 2, 7)),
+        }]
+    );
+}
+
+#[test]
+fn comments_dont_start_in_strings() {
+    let c = Token::Identifier("c".to_string());
+    assert_eq!(
+        tokens("\"a // b\" c"),
+        [Token::StringLiteral("a // b".to_string()), c.clone()]
+    );
+    assert_eq!(
+        tokens("\"a /* b\" c"),
+        [Token::StringLiteral("a /* b".to_string()), c]
+    );
+}
+
+#[test]
+fn strings_are_escaped_back_into_rust() {
+    let source = r#"region Strings { function f() { return "a\"b\\c\nd\x01"; } }"#;
+    let mut region = parse_region(source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let code = gen_code(vec![region], vec![], "test");
+    assert!(code.contains(r#""a\"b\\c\nd\u{1}""#), "{code}");
+}
+
+#[test]
+fn block_comments_nest() {
+    let a = Token::Identifier("a".to_string());
+    let e = Token::Identifier("e".to_string());
+    assert_eq!(tokens("a /* b /* c */ d */ e"), [a.clone(), e.clone()]);
+    assert_eq!(tokens("a/**/e"), [a.clone(), e.clone()]);
+    assert_eq!(tokens("a /* b **/ e // f */"), [a, e]);
+}
+
+#[test]
+fn unterminated_block_comments_are_errors() {
+    let (_, errors) = Tokeniser::tokenise("a /* b /* c */\nd");
+    assert_eq!(
+        errors,
+        [LexError {
+            message: "unterminated block comment".to_string(),
+            text: "/* b /* c */\nd".to_string(),
+            span: span((2, 1, 3), (16, 2, 2)),
+        }]
+    );
+}
+
+#[test]
+fn doc_comments_belong_to_the_next_function_or_region() {
+    let source = "/// a region
+        region R {
+            /// adds one
+            /// to x
+            function f(x) { return x + 1; }
+            // not a doc comment
+            function g() { }
+        }";
+    let program = parse_program(source).unwrap();
+    let region = &program.regions[0];
+    assert_eq!(region.doc.as_deref(), Some("a region"));
+
+    let docs = region
+        .body
+        .iter()
+        .map(|item| match item {
+            RegionItem::Function(function) => function.doc.as_deref(),
+            item => panic!("expected a function, got {item:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(docs, [Some("adds one\nto x"), None]);
+}
+
+#[test]
+fn every_lex_error_is_reported() {
+    let source = "let a = 1 $ 2;\nlet b = \"\\q\" & c;\nlet d = #;";
+    assert_eq!(
+        lex_errors(source),
+        [
+            "1:11: unexpected character `$`",
+            "2:9: invalid escape sequence \\q",
+            "2:14: incomplete operator",
+            "3:9: unexpected character `#`",
+        ]
+    );
+}
+
+#[test]
+fn tokens_know_where_they_are() {
+    let (tokens, errors) = Tokeniser::tokenise("/* é */ café = 1;\n  x");
+    assert_eq!(errors, []);
+
+    let spans = tokens.iter().map(|t| t.span).collect::<Vec<_>>();
+    assert_eq!(
+        spans,
+        [
+            // offsets count bytes, columns count characters
+            span((9, 1, 9), (14, 1, 13)),
+            span((15, 1, 14), (16, 1, 15)),
+            span((17, 1, 16), (18, 1, 17)),
+            span((18, 1, 17), (19, 1, 18)),
+            span((22, 2, 3), (23, 2, 4)),
+        ]
+    );
+}
+
+#[test]
+fn expressions_know_where_they_are() {
+    let expr = parse_expr("café +\n (b * 2)").unwrap();
+    // after `region test { `
+    assert_eq!(expr.span, span((14, 1, 15), (30, 2, 9)));
+
+    let ExprKind::Binary(lhs, _, rhs) = &expr.kind else {
+        panic!("expected a binary expression, got {}", sexpr(&expr));
+    };
+    assert_eq!(lhs.span, span((14, 1, 15), (19, 1, 19)));
+    // the brackets are part of the sum, but not of the product in them
+    assert_eq!(rhs.span, span((24, 2, 3), (29, 2, 8)));
+}
+
+#[test]
+fn minus_is_subtraction_after_an_operand() {
+    assert_parses_as("a-1", "(Sub a 1)");
+    assert_parses_as("a - 1", "(Sub a 1)");
+    assert_parses_as("a -1", "(Sub a 1)");
+    assert_parses_as("a - -1", "(Sub a (Neg 1))");
+}
+
+#[test]
+fn minus_is_negation_before_an_operand() {
+    assert_parses_as("-a", "(Neg a)");
+    assert_parses_as("-1", "(Neg 1)");
+    assert_parses_as("f(-1)", "(f (Neg 1))");
+    assert_parses_as("f(a, -1)", "(f a (Neg 1))");
+}
+
+#[test]
+fn negated_literals_are_folded() {
+    assert_cleans_as("f(-1)", "(f -1)");
+    assert_cleans_as("f(--1)", "(f 1)");
+    assert_cleans_as("f(a -1)", "(f (Sub a 1))");
+    assert_cleans_as("f(-a)", "(f (Neg a))");
+    assert_cleans_as("f(a * -2)", "(f (Mult a -2))");
+}
+
+#[test]
+fn division_rounds_down_like_in_python() {
+    assert_cleans_as("f(7 / 2)", "(f 3)");
+    assert_cleans_as("f(-7 / 2)", "(f -4)");
+    assert_cleans_as("f(7 / -2)", "(f -4)");
+    assert_cleans_as("f(-7 / -2)", "(f 3)");
+    assert_cleans_as("f(-6 / 2)", "(f -3)");
+    assert_cleans_as("f(-7 % 2)", "(f 1)");
+    assert_cleans_as("f(7 % -2)", "(f -1)");
+    assert_cleans_as("f(-7 % -2)", "(f -1)");
+    assert_cleans_as("f(-6 % 4)", "(f 2)");
+    assert_cleans_as("f(-7.5 % 2.0)", "(f 0.5)");
+    assert_cleans_as("f(7.5 % -2.0)", "(f -0.5)");
+}
+
+#[test]
+fn arithmetic_is_only_left_out_on_numbers() {
+    assert_cleans_as("f(i + 0)", "(f i)");
+    assert_cleans_as("f(0 + i)", "(f i)");
+    assert_cleans_as("f(i - 0)", "(f i)");
+    assert_cleans_as("f(x - 0)", "(f x)");
+    assert_cleans_as("f(i * 1)", "(f i)");
+    assert_cleans_as("f(x * 1)", "(f x)");
+    assert_cleans_as("f(i / 1)", "(f i)");
+    assert_cleans_as("f(i * 0)", "(f 0)");
+
+    // -0.0 + 0 is 0.0, and a float times 0 is a float
+    assert_cleans_as("f(x + 0)", "(f (Add x 0))");
+    assert_cleans_as("f(x * 0)", "(f (Mult x 0))");
+    assert_cleans_as("f(0 * x)", "(f (Mult 0 x))");
+
+    // `a` might not be a number, so it has to raise a TypeError
+    assert_cleans_as("f(a + 0)", "(f (Add a 0))");
+    assert_cleans_as("f(a - 0)", "(f (Sub a 0))");
+    assert_cleans_as("f(a * 1)", "(f (Mult a 1))");
+    assert_cleans_as("f(a * 0)", "(f (Mult a 0))");
+    assert_cleans_as("f(a / 1)", "(f (Div a 1))");
+}
+
+#[test]
+fn comparisons_and_logic_fold_to_booleans() {
+    assert_cleans_as("f(1 < 2)", "(f true)");
+    assert_cleans_as("f(!0)", "(f true)");
+    assert_cleans_as("f(true && !true)", "(f false)");
+    assert_cleans_as("f(none == none)", "(f true)");
+    assert_cleans_as("f(\"a\" != 1)", "(f true)");
+    assert_cleans_as("f(true + 1)", "(f 2)");
+}
+
+#[test]
+fn short_circuits_fold_without_the_rhs() {
+    assert_cleans_as("f(false && a)", "(f false)");
+    assert_cleans_as("f(true || a)", "(f true)");
+    assert_cleans_as("f(true && a)", "(f (And true a))");
+    assert_cleans_as("f(a || true)", "(f (Or a true))");
+}
+
+#[test]
+fn type_annotations_are_optional() {
+    let source =
+        "region test { function f(n: int, xs: list<list<str>>, a) -> buffer { let b: bool = n; } }";
+    let region = parse_region(source).unwrap();
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+
+    let list_of_lists = Type::List(Box::new(Type::List(Box::new(Type::Str))));
+    let param_types = function.params.iter().map(|p| p.var_type.clone());
+    assert_eq!(
+        param_types.collect::<Vec<_>>(),
+        [Some(Type::Int), Some(list_of_lists), None]
+    );
+    assert_eq!(function.return_type, Some(Type::Buffer));
+    assert!(matches!(
+        function.body[0].kind,
+        StatementKind::Let(_, Some(Type::Bool), _)
+    ));
+}
+
+#[test]
+fn array_literals_can_be_nested() {
+    assert_parses_as(
+        "[1, a + 1, [2, [b]], []]",
+        "(Array 1 (Add a 1) (Array 2 (Array b)) (Array))",
+    );
+    assert_parses_as("[a, b][0]", "(Index (Array a b) 0)");
+    assert_cleans_as("f([1 + 1, -2.5])", "(f (Array 2 -2.5))");
+}
+
+#[test]
+fn dict_literals_are_expressions() {
+    assert_parses_as("{a: 1, b: c + 1}", "(Dict (a 1) (b (Add c 1)))");
+    assert_parses_as("{}[k]", "(Index (Dict) k)");
+
+    // a condition followed by a block isn't a dict
+    let source = "region test { let d: dict<str, list<int>> = {}; if d { d[k] = {}; } }";
+    let region = parse_region(source).unwrap();
+    let [RegionItem::Statement(declaration), RegionItem::Statement(condition)] =
+        region.body.as_slice()
+    else {
+        panic!("expected two statements, got {:?}", region.body);
+    };
+
+    let list_of_ints = Type::List(Box::new(Type::Int));
+    let dict_type = Type::Dict(Box::new(Type::Str), Box::new(list_of_ints));
+    assert!(matches!(
+        &declaration.kind,
+        StatementKind::Let(_, Some(var_type), _) if *var_type == dict_type
+    ));
+    assert!(matches!(condition.kind, StatementKind::If(_, _)));
+}
+
+#[test]
+fn for_in_loops_over_any_expression() {
+    let source =
+        "region test { for x in range(n, 0, -1) { total = total + x; } for k in {a: 1} { } }";
+    let region = parse_region(source).unwrap();
+
+    let loops = region.body.iter().map(|item| match item {
+        RegionItem::Statement(statement) => match &statement.kind {
+            StatementKind::ForIn(var, iter, body) => (var.as_str(), sexpr(iter), body.len()),
+            kind => panic!("expected a for-in loop, got {kind:?}"),
+        },
+        item => panic!("expected a statement, got {item:?}"),
+    });
+    assert_eq!(
+        loops.collect::<Vec<_>>(),
+        [
+            ("x", "(range n 0 (Neg 1))".to_string(), 1),
+            ("k", "(Dict (a 1))".to_string(), 0)
+        ]
+    );
+}
+
+#[test]
+fn while_loops_can_break_and_continue() {
+    let source = "region test { while (i < n) { if (a) { break; } continue; } }";
+    let region = parse_region(source).unwrap();
+    let [RegionItem::Statement(statement)] = region.body.as_slice() else {
+        panic!("expected a single statement, got {:?}", region.body);
+    };
+    let StatementKind::While(cond, body) = &statement.kind else {
+        panic!("expected a while loop, got {:?}", statement.kind);
+    };
+
+    assert_eq!(sexpr(cond), "(LessThan i n)");
+    let [if_break, continue_] = body.as_slice() else {
+        panic!("expected two statements, got {body:?}");
+    };
+    let StatementKind::If(cond, if_body) = &if_break.kind else {
+        panic!("expected an if, got {:?}", if_break.kind);
+    };
+    assert_eq!(sexpr(cond), "a");
+    assert!(matches!(
+        if_body[..],
+        [Statement {
+            kind: StatementKind::Break,
+            ..
+        }]
+    ));
+    assert!(matches!(continue_.kind, StatementKind::Continue));
+}
+
+#[test]
+fn code_after_break_and_continue_is_removed() {
+    let source = "region test { function test(n) {
+        while (n > 0) { n = n - 1; continue; n = 0; }
+        for x in n { break; f(x); }
+    } }";
+    let mut region = parse_region(source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+    let loop_bodies = function
+        .body
+        .iter()
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::While(_, body) | StatementKind::ForIn(_, _, body) => Some(body),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let [while_body, for_body] = loop_bodies.as_slice() else {
+        panic!("expected two loops, got {:?}", function.body);
+    };
+    assert!(matches!(
+        while_body[..],
+        [
+            _,
+            Statement {
+                kind: StatementKind::Continue,
+                ..
+            }
+        ]
+    ));
+    assert!(matches!(
+        for_body[..],
+        [Statement {
+            kind: StatementKind::Break,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn else_if_chains_are_nested_if_elses() {
+    /// the statements as s-expressions, with an if written `(if cond then else)`
+    fn branches(statements: &[Statement]) -> String {
+        let statements = statements.iter().map(|statement| match &statement.kind {
+            StatementKind::If(cond, body) => format!("(if {} [{}])", sexpr(cond), branches(body)),
+            StatementKind::IfElse(cond, if_body, else_body) => format!(
+                "(if {} [{}] [{}])",
+                sexpr(cond),
+                branches(if_body),
+                branches(else_body)
+            ),
+            StatementKind::Expression(expr) => sexpr(expr),
+            kind => panic!("unexpected statement {kind:?}"),
+        });
+        statements.collect::<Vec<_>>().join(" ")
+    }
+
+    let parse = |source: &str| {
+        let region = parse_region(&format!("region test {{ {source} }}")).unwrap();
+        let statements = region.body.into_iter().map(|item| match item {
+            RegionItem::Statement(statement) => statement,
+            item => panic!("expected a statement, got {item:?}"),
+        });
+        branches(&statements.collect::<Vec<_>>())
+    };
+
+    assert_eq!(
+        parse("if (a) { f(1); } else if (b) { f(2); } else if (c) { f(3); } else { f(4); }"),
+        "(if a [(f 1)] [(if b [(f 2)] [(if c [(f 3)] [(f 4)])])])"
+    );
+    assert_eq!(
+        parse("if (a) { f(1); } else if (b) { f(2); }"),
+        "(if a [(f 1)] [(if b [(f 2)])])"
+    );
+}
+
+#[test]
+fn else_if_chains_only_return_when_every_arm_does() {
+    let body_len = |body: &str| {
+        let source = format!("region test {{ function test(a, b) {{ {body} }} }}");
+        let mut region = parse_region(&source).unwrap();
+        clean_ast(&mut region, "test");
+        let [RegionItem::Function(function)] = region.body.as_slice() else {
+            panic!("expected a single function, got {:?}", region.body);
+        };
+        function.body.len()
+    };
+
+    // the `return none` at the end of the function is only removed when the
+    // chain always returns
+    let every_arm = "if (a) { return 1; } else if (b) { return 2; } else { return 3; }";
+    assert_eq!(body_len(every_arm), 1);
+    let no_else = "if (a) { return 1; } else if (b) { return 2; }";
+    assert_eq!(body_len(no_else), 2);
+    let middle_arm = "if (a) { return 1; } else if (b) { f(2); } else { return 3; }";
+    assert_eq!(body_len(middle_arm), 2);
+}
+
+#[test]
+fn try_has_catches_in_order() {
+    let source = "region test { try { raise ValueError(x); } catch (e: KeyError) { } catch (e) { raise e; } }";
+    let region = parse_region(source).unwrap();
+    let [RegionItem::Statement(statement)] = region.body.as_slice() else {
+        panic!("expected a single statement, got {:?}", region.body);
+    };
+    let StatementKind::Try(body, catches) = &statement.kind else {
+        panic!("expected a try block, got {:?}", statement.kind);
+    };
+
+    assert!(matches!(
+        &body[0].kind,
+        StatementKind::Raise(expr) if sexpr(expr) == "(ValueError x)"
+    ));
+    let catches = catches.iter().map(|catch| {
+        (
+            catch.var.as_str(),
+            catch.exception.as_deref(),
+            catch.body.len(),
+        )
+    });
+    assert_eq!(
+        catches.collect::<Vec<_>>(),
+        [("e", Some("KeyError"), 0), ("e", None, 1)]
+    );
+}
+
+#[test]
+fn lambdas_capture_outer_variables() {
+    let source = "region test { function test(a, b) { let f = fn (x) { b = b + x; return a + b; }; f(1); } }";
+    let mut region = parse_region(source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+    let exprs = function.body[..2]
+        .iter()
+        .map(|statement| match &statement.kind {
+            StatementKind::Let(_, _, expr) | StatementKind::Expression(expr) => sexpr(expr),
+            kind => panic!("unexpected statement {kind:?}"),
+        });
+
+    // assigning to a variable from outside captures it, and calling a variable
+    // calls its value
+    assert_eq!(exprs.collect::<Vec<_>>(), ["(fn b a)", "(call f 1)"]);
+}
+
+#[test]
+fn lambdas_are_only_the_same_as_themselves() {
+    let lambda = parse_expr("fn (x) { return x; }").unwrap();
+    let other = parse_expr("fn (x) { return x; }").unwrap();
+    assert_eq!(lambda.span, other.span);
+    assert_eq!(lambda, lambda.clone());
+    assert_ne!(lambda, other);
+}
+
+#[test]
+fn nested_lambdas_are_cleaned_once() {
+    // cleaning each lambda again for every pass over the ones around it
+    // would take 9^20 passes
+    let nested = (0..20).fold("return a;".to_string(), |body, _| {
+        format!("let f = fn (b) {{ {body} }}; return f;")
+    });
+    let source = format!("region test {{ function test(a) {{ {nested} }} }}");
+    let mut region = parse_region(&source).unwrap();
+    clean_ast(&mut region, "test");
+}
+
+#[test]
+fn region_lets_used_by_functions_are_globals() {
+    let source = "region test { const N = 2 * 3; let a = 1; let b = a + N; let c = 5; blackbox(c); function f() { b = b + N; } }";
+    let mut region = parse_region(source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let globals = region.body.iter().filter_map(|item| match item {
+        RegionItem::Global(global) => {
+            Some((global.name.as_str(), global.constant, sexpr(&global.value)))
+        }
+        _ => None,
+    });
+    // `a` is needed for the value of `b`, but only `main` uses `c`
+    assert_eq!(
+        globals.collect::<Vec<_>>(),
+        [
+            ("N", true, "6".to_string()),
+            ("a", false, "1".to_string()),
+            ("b", false, "(Add (global a) 6)".to_string())
+        ]
+    );
+
+    let Some(RegionItem::Function(f)) = region
+        .body
+        .iter()
+        .find(|item| matches!(item, RegionItem::Function(f) if f.name == "f"))
+    else {
+        panic!("expected a function `f`, got {:?}", region.body);
+    };
+    assert!(matches!(
+        &f.body[0].kind,
+        StatementKind::GlobalAssignment(name, expr) if name == "b" && sexpr(expr) == "(Add (global b) 6)"
+    ));
+}
+
+#[test]
+fn python_imports_and_keyword_arguments() {
+    assert_parses_as("f(x, key=1)", "(f x key=1)");
+    assert_parses_as("a.b(key=c == 2)", "(.b a key=(Equal c 2))");
+
+    let source = "region test { import math; from os.path import join; function f(math) { return [join(math, sep=1), sqrt(2)]; } function g() { return math.sqrt(2); } }";
+    let mut region = parse_region(source).unwrap();
+    let imports = region
+        .python_imports
+        .iter()
+        .map(|import| (import.module.as_str(), import.attribute.as_deref()));
+    assert_eq!(
+        imports.collect::<Vec<_>>(),
+        [("math", None), ("os.path", Some("join"))]
+    );
+
+    clean_ast(&mut region, "test");
+    let returned = region
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            RegionItem::Function(function) => match &function.body[0].kind {
+                StatementKind::Return(expr) => Some(expr),
+                _ => None,
+            },
+            _ => None,
+        })
+        .map(|expr| match &expr.kind {
+            ExprKind::Array(elements) => elements.iter().map(sexpr).collect::<Vec<_>>().join(" "),
+            _ => sexpr(expr),
+        });
+    // the parameter `math` hides the module
+    assert_eq!(
+        returned.collect::<Vec<_>>(),
+        [
+            "(call (import join) math sep=1) (sqrt 2)",
+            "(.sqrt (import math) 2)"
+        ]
+    );
+}
+
+#[test]
+fn loop_invariants_only_move_out_when_they_cant_raise() {
+    assert_eq!(
+        hoisted("let t = 0; while (t < n) { t = t + a * b; } return t;"),
+        ["(Mult a b)"]
+    );
+    // the loop might not run at all, so `b` might be zero
+    assert_eq!(
+        hoisted("let t = 0; for (let i = 0; i < n; i = i + 1) { t = t + a % b; } return t;"),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        hoisted("let t = 0; let i = 0; while (i < n) { t = t + a / b; i = i + 1; } return t;"),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        hoisted(
+            "let t = 0; for (let i = 0; i < n; i = i + 1) { t = t + a / 2 + a % -1; } return t;"
+        ),
+        ["(Div a 2)"]
+    );
+    // `s` might not be a number
+    assert_eq!(
+        hoisted("let t = 0; while (t < n) { t = t + (s * 2) + (a * 2); } return t;"),
+        ["(Mult a 2)"]
+    );
+    // conditions only run sometimes
+    assert_eq!(
+        hoisted("let t = 0; let i = 0; while (i < n) { if (b != 0) { t = t + (a - b); } i = i + 1; } return t;"),
+        ["(NotEqual b 0)"]
+    );
+}
+
+#[test]
+fn declarations_without_a_value_stay_in_the_loop() {
+    assert_eq!(
+        hoisted("let i = 0; while (i < n) { let y; y = i; i = i + 1; } return i;"),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn allocations_are_never_shared() {
+    // each iteration gets its own buffer
+    assert_eq!(
+        hoisted("let out = []; for i in range(n) { let buf = allocate(2); buf[0] = i; out.append(buf); } return out;"),
+        Vec::<String>::new()
+    );
+
+    let source = "region test { function test(n) { let x = allocate(n); let y = allocate(n); return [x, y]; } }";
+    let mut region = parse_region(source).unwrap();
+    clean_ast(&mut region, "test");
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+    assert!(matches!(
+        &function.body[1].kind,
+        StatementKind::Let(name, _, value) if name == "y" && sexpr(value) == "(allocate n)"
+    ));
+}
+
+#[test]
+fn unknown_types_are_rejected() {
+    for (source, message) in [
+        ("let x: integer = 1;", "unknown type `integer`"),
+        ("let x: list = 1;", "`list` needs an item type"),
+        ("let x: int<str> = 1;", "`int` doesn't take an item type"),
+        ("let x: dict<str> = 1;", "`dict` needs key and value types"),
+        (
+            "let x: list<int, int> = 1;",
+            "`list` doesn't take key and value types",
+        ),
+    ] {
+        match parse_region(&format!("region test {{ {source} }}")) {
+            Ok(_) => panic!("`{source}` should not parse"),
+            Err(e) => assert!(e.contains(message), "parsing `{source}` gave: {e}"),
+        }
+    }
+}
+
+#[test]
+fn temp_variables_dont_take_names_from_the_source() {
+    let source = "region test { function test(a: int, b: int, n: int) {
+        let __temp_0 = 1;
+        let acc = 0;
+        for (let i = 0; i < n; i = i + 1) { acc = acc + a * b; }
+        return acc + __temp_0;
+    } }";
+    let mut region = parse_region(source).unwrap();
+    clean_ast(&mut region, "test");
+
+    let [RegionItem::Function(function)] = region.body.as_slice() else {
+        panic!("expected a single function, got {:?}", region.body);
+    };
+    let lets = function
+        .body
+        .iter()
+        .filter_map(|statement| match &statement.kind {
+            StatementKind::Let(name, _, value) => Some(format!("{name} = {}", sexpr(value))),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(lets, ["__temp_0 = 1", "acc = 0", "__temp_1 = (Mult a b)"]);
+}
+
+#[test]
+fn loops_in_both_branches_of_an_if_are_optimized() {
+    let in_branches = "let acc = 0;
+        if (n > 1) {
+            for (let i = 0; i < n; i = i + 1) { acc = acc + a * b; }
+        } else {
+            for (let i = 0; i < n; i = i + 1) { acc = acc + (a - b); }
+        }
+        return acc;";
+    assert_eq!(hoisted(in_branches), ["(Mult a b)", "(Sub a b)"]);
+
+    let in_else_if = "let acc = 0;
+        if (n > 1) {
+            acc = 1;
+        } else if (n > 0) {
+            while (acc < n) { acc = acc + a * b; }
+        }
+        return acc;";
+    assert_eq!(hoisted(in_else_if), ["(Mult a b)"]);
+}
+
+#[test]
+fn calls_to_other_regions_are_resolved() {
+    let source = "
+        region A { function f(x) { return x; } }
+        region B { use A.f; function g(A) { A.f(1); } function h() { A.f(2); } function k() { f(3); } }
+    ";
+    let mut regions = parse_program(source).unwrap().regions;
+    let functions = region_functions(regions.iter().map(|region| ("test", region)));
+    for region in &mut regions {
+        resolve_region_calls(region, &functions, "test");
+    }
+
+    let calls = regions[1].body.iter().map(|item| match item {
+        RegionItem::Function(function) => match &function.body[0].kind {
+            StatementKind::Expression(expr) => sexpr(expr),
+            kind => panic!("expected an expression statement, got {kind:?}"),
+        },
+        item => panic!("expected a function, got {item:?}"),
+    });
+
+    // a parameter with the same name as a region hides it, and used functions
+    // don't need the region name
+    assert_eq!(
+        calls.collect::<Vec<_>>(),
+        ["(.f A 1)", "(A.f 2)", "(A.f 3)"]
+    );
+}
@@ -1,37 +1,72 @@
+mod clean_ast;
 pub mod codegen;
 mod grammar_ast;
-mod clean_ast;
+mod span;
 use lalrpop_util::lalrpop_mod;
-lalrpop_mod!(grammar);
+use lalrpop_util::ParseError;
+lalrpop_mod!(
+    #[allow(clippy::all, unused_imports)]
+    grammar
+);
 
-use grammar::RegionParser;
+use grammar::ProgramParser;
 
 pub mod token_fsm;
 
+#[cfg(test)]
+mod grammar_tests;
+
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 
+use grammar_ast::{Program, Region};
+use span::{Position, Span};
 use std::process::Command;
 use strum::AsRefStr;
 use strum::Display;
 use strum::EnumIter;
-use token_fsm::Tokeniser;
+use token_fsm::{LexError, Tokeniser};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
-    Identifier(String), // [a-zA-Z][a-zA-Z0-9]*
-    Number(String),     // -?[0-9]+(.[0-9]+)?
-    Keyword(Keyword),   // region, let, function, return, if, for
-    Operator(Operator), // +, -, *, /, =, <, >, <=, >=
-    Lcur,               // Left curly brace {
-    Rcur,               // Right curly brace }
-    Lpar,               // Left parenthesis (
-    Rpar,               // Right parenthesis )
-    Semi,               // Semicolon ;
-    Comma,              // Comma ,
-    Dot,                // Dot .
-    Lbrack,             // Left square bracket [
-    Rbrack,             // Right square bracket ]
+    Identifier(String),    // (XID_Start | _) XID_Continue*
+    Number(String),        // [0-9]+(.[0-9]*)?
+    StringLiteral(String), // "...", with escape sequences decoded
+    Keyword(Keyword),      // region, let, function, if, while, true, none, etc.
+    Operator(Operator),    // +, -, *, /, %, =, ==, !=, <, >, <=, >=, &&, ||, !, ->
+    Lcur,                  // Left curly brace {
+    Rcur,                  // Right curly brace }
+    Lpar,                  // Left parenthesis (
+    Rpar,                  // Right parenthesis )
+    Semi,                  // Semicolon ;
+    Comma,                 // Comma ,
+    Dot,                   // Dot .
+    Colon,                 // Colon :
+    Lbrack,                // Left square bracket [
+    Rbrack,                // Right square bracket ]
+}
+
+/// A token, along with the span of source code it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    /// The `///` doc comments just before this token, one per line.
+    /// They aren't part of the grammar, but the parser attaches them to the
+    /// function or region they document.
+    pub doc_comments: Vec<String>,
+}
+
+impl SpannedToken {
+    pub fn new(token: Token, span: Span, doc_comments: Vec<String>) -> Self {
+        Self {
+            token,
+            span,
+            doc_comments,
+        }
+    }
 }
 
 impl Token {
@@ -41,17 +76,21 @@ impl Token {
         match self {
             Token::Identifier(value) => format!("<Identifier, {}>", value),
             Token::Number(value) => format!("<Number, {}>", value),
+            Token::StringLiteral(value) => {
+                format!("<StringLiteral, \"{}\">", value.escape_default())
+            }
             Token::Keyword(value) => format!("<Keyword, {}>", value.as_ref()),
             Token::Operator(value) => format!("<Operator, {}>", value.as_ref()),
-            Token::Lcur => format!("<Lcur, {{>"),
-            Token::Rcur => format!("<Rcur, }}>"),
-            Token::Lpar => format!("<Lpar, (>"),
-            Token::Rpar => format!("<Rpar, )>"),
-            Token::Semi => format!("<Semi, ;>"),
-            Token::Comma => format!("<Comma, ,>"),
-            Token::Dot => format!("<Dot, .>"),
-            Token::Lbrack => format!("<Lbrack, [>"),
-            Token::Rbrack => format!("<Rbrack, ]>"),
+            Token::Lcur => "<Lcur, {>".to_string(),
+            Token::Rcur => "<Rcur, }>".to_string(),
+            Token::Lpar => "<Lpar, (>".to_string(),
+            Token::Rpar => "<Rpar, )>".to_string(),
+            Token::Semi => "<Semi, ;>".to_string(),
+            Token::Comma => "<Comma, ,>".to_string(),
+            Token::Dot => "<Dot, .>".to_string(),
+            Token::Colon => "<Colon, :>".to_string(),
+            Token::Lbrack => "<Lbrack, [>".to_string(),
+            Token::Rbrack => "<Rbrack, ]>".to_string(),
         }
     }
 }
@@ -78,6 +117,54 @@ pub enum Keyword {
 
     #[strum(serialize = "for")]
     For,
+
+    #[strum(serialize = "while")]
+    While,
+
+    #[strum(serialize = "break")]
+    Break,
+
+    #[strum(serialize = "continue")]
+    Continue,
+
+    #[strum(serialize = "true")]
+    True,
+
+    #[strum(serialize = "false")]
+    False,
+
+    #[strum(serialize = "none")]
+    None,
+
+    #[strum(serialize = "import")]
+    Import,
+
+    #[strum(serialize = "use")]
+    Use,
+
+    #[strum(serialize = "class")]
+    Class,
+
+    #[strum(serialize = "in")]
+    In,
+
+    #[strum(serialize = "try")]
+    Try,
+
+    #[strum(serialize = "catch")]
+    Catch,
+
+    #[strum(serialize = "raise")]
+    Raise,
+
+    #[strum(serialize = "fn")]
+    Fn,
+
+    #[strum(serialize = "const")]
+    Const,
+
+    #[strum(serialize = "from")]
+    From,
 }
 
 #[derive(AsRefStr, Display, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -94,9 +181,18 @@ pub enum Operator {
     #[strum(serialize = "/")]
     Div,
 
+    #[strum(serialize = "%")]
+    Mod,
+
     #[strum(serialize = "=")]
     Assign,
 
+    #[strum(serialize = "==")]
+    Equal,
+
+    #[strum(serialize = "!=")]
+    NotEqual,
+
     #[strum(serialize = "<")]
     LessThan,
 
@@ -108,28 +204,18 @@ pub enum Operator {
 
     #[strum(serialize = ">=")]
     GreaterThanOrEqual,
-}
 
-#[derive(Debug)]
-enum SyntaxNode {
-    Region {
-        name: String,
-        body: Vec<SyntaxNode>,
-    },
-    Assignment {
-        assignee: Token,
-        assigner: Box<SyntaxNode>,
-    },
-    FunctionDefinition {
-        name: Token,
-        parameters: Vec<Token>,
-        body: Vec<SyntaxNode>,
-    }, // Changed
-    FunctionCall {
-        name: Token,
-        arguments: Vec<Box<SyntaxNode>>,
-    }, // Changed
-    Value(Token),
+    #[strum(serialize = "&&")]
+    And,
+
+    #[strum(serialize = "||")]
+    Or,
+
+    #[strum(serialize = "!")]
+    Not,
+
+    #[strum(serialize = "->")]
+    Arrow,
 }
 
 fn main() {
@@ -140,86 +226,31 @@ fn main() {
         .cloned()
         .unwrap_or("example_input_source_code\\full.txt".to_string());
 
-    let source_code = fs::read_to_string(&file_path);
-    let mut source_code_string: Option<String> = None;
-
-    match source_code {
-        Ok(source_code_str) => {
-            println!("Source code: \n{source_code_str}");
-            source_code_string = Some(source_code_str);
-        }
-        Err(error) => {
-            eprintln!("Error reading from file:\n{error}");
-            std::process::exit(1);
-        }
-    }
-
-    let mut code_lines_without_comments = vec![];
-    if let Some(ref code_with_comments) = source_code_string {
-        for line in code_with_comments.lines() {
-            let line_without_comments = line.split("//").next().unwrap();
-            code_lines_without_comments.push(line_without_comments);
-        }
-    } else {
-        eprintln!("No source code in file :)");
-        std::process::exit(1);
-    }
-
-    let code_without_comments = code_lines_without_comments.join("\n");
-
-    println!("1. Code without comments:");
-    for line in &code_lines_without_comments {
-        println!("{line}");
-    }
+    // load the file and everything it imports. the file itself is loaded last
+    let mut files = vec![];
+    let root = Path::new(&file_path);
+    let root = root.canonicalize().unwrap_or(root.to_path_buf());
+    load_file(&file_path, root, &mut vec![], &mut files);
 
-    let tokens_result = Tokeniser::tokenise(&file_path, &code_without_comments);
+    println!("\n5. Generated code:");
 
-    let tokens = match tokens_result {
-        Ok(tokens) => tokens,
-        Err(error) => {
-            eprintln!("\n{error}");
-            std::process::exit(1);
+    let functions = clean_ast::region_functions(
+        files
+            .iter()
+            .flat_map(|file| file.regions.iter().map(|r| (file.path.as_str(), r))),
+    );
+    for file in &mut files {
+        for region in &mut file.regions {
+            clean_ast::resolve_region_calls(region, &functions, &file.path);
+            clean_ast::clean_ast(region, &file.path);
         }
-    };
-
-    println!("\n3. Tokens:");
-    for token in &tokens {
-        print!("{} ", token.fmt_type_and_value());
-    }
-
-    let mut serialized_tokens = String::new();
-    println!("\n3.5. Serialized Tokens:"); // Updated label for clarity
-    for token in &tokens {
-        // Here we get the formatted string for each token
-        let token_str = token.fmt_type_and_value();
-        serialized_tokens.push_str(&token_str);
-        serialized_tokens.push(' '); // Add a space between tokens
     }
 
-    // Trim any trailing whitespace for clean output
-    serialized_tokens = serialized_tokens.trim_end().to_string();
-
-    // Print the serialized tokens to verify correctness
-    println!("Serialized Tokens: {}", serialized_tokens);
-
-    println!("\n4. SerializedTokens:");
-    println!("Serialized Tokens: {:?}", serialized_tokens);
-
-    let tokens_str = serialized_tokens.clone();
-    let mut region = match RegionParser::new().parse(&tokens_str) {
-        Ok(region) => region,
-        Err(e) => {
-            handle_ast_error(serialized_tokens, e);
-            return;
-        }
-    };
-
-    println!("Parsed AST: {:#?}", region);
-
-    println!("\n5. Generated code:");
-
-    clean_ast::clean_ast(&mut region);
-    let code = codegen::gen_code(region);
+    // only the regions in the file itself are visible from python, imported
+    // ones are just there to be called
+    let exported = files.pop().unwrap().regions;
+    let imported = files.into_iter().flat_map(|file| file.regions).collect();
+    let code = codegen::gen_code(exported, imported, &package_name(&file_path));
     println!("\n{code}");
 
     // write code to output.rs and pybind_test/auto.rs
@@ -244,12 +275,7 @@ fn main() {
 
     // run cargo fix on pybind_test (swallow warnings in autogenerated code)
     Command::new("cargo")
-        .args([
-            "fix",
-            "--lib",
-            "--allow-dirty",
-            "--allow-staged",
-        ])
+        .args(["fix", "--lib", "--allow-dirty", "--allow-staged"])
         .current_dir("pybind_test")
         .output()
         .expect("Failed to run cargo fix");
@@ -257,48 +283,205 @@ fn main() {
     println!();
 }
 
-fn handle_ast_error(
-    serialized_tokens: String,
-    e: lalrpop_util::ParseError<usize, lalrpop_util::lexer::Token, &str>,
+/// a file that was loaded, and the regions in it
+struct SourceFile {
+    path: String,       // As written in the import, for error messages
+    canonical: PathBuf, // To tell if two imports are the same file
+    regions: Vec<Region>,
+}
+
+/// parse a file and every file it imports, adding them to `files` in the
+/// order they need to be loaded in. `importing` is the chain of imports that
+/// led to this file, to find import cycles.
+fn load_file(
+    file_path: &str,
+    canonical: PathBuf,
+    importing: &mut Vec<(String, PathBuf)>,
+    files: &mut Vec<SourceFile>,
 ) {
-    println!("Error parsing: {:?}", e);
-    println!("Full error: {:?}", e);
+    let program = parse_file(file_path);
+    importing.push((file_path.to_string(), canonical.clone()));
+
+    // imports are relative to the file they are in
+    let dir = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    for import in &program.imports {
+        let import_path = dir.join(&import.path);
+        let Ok(import_canonical) = import_path.canonicalize() else {
+            eprintln!(
+                "Error in file {file_path}:{}: can't find imported file `{}`",
+                import.span, import.path
+            );
+            std::process::exit(1);
+        };
+
+        if let Some(i) = importing.iter().position(|(_, p)| *p == import_canonical) {
+            let cycle = importing[i..]
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .chain([importing[i].0.as_str()])
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            eprintln!(
+                "Error in file {file_path}:{}: import cycle: {cycle}",
+                import.span
+            );
+            std::process::exit(1);
+        }
 
-    if let lalrpop_util::ParseError::InvalidToken { location } = e {
-        println!("Invalid token at character location: {}", location);
+        // each file is only loaded once, however many times it is imported
+        if files.iter().any(|file| file.canonical == import_canonical) {
+            continue;
+        }
 
-        // Split `serialized_tokens` into individual tokens
-        let tokens: Vec<&str> = serialized_tokens.split_whitespace().collect();
+        let import_path = import_path.to_string_lossy();
+        load_file(&import_path, import_canonical, importing, files);
+    }
 
-        // Convert character location to token index
-        let mut char_count = 0;
-        let problematic_token_index = tokens
-            .iter()
-            .position(|&token| {
-                char_count += token.len() + 1; // +1 for the space separator
-                char_count > location
-            })
-            .unwrap_or_else(|| tokens.len().saturating_sub(1));
-
-        // Identify the problematic token
-        let problematic_token = tokens
-            .get(problematic_token_index)
-            .unwrap_or(&"Unknown token");
-        println!("Problematic token: {}", problematic_token);
-
-        // Define the context range to show surrounding tokens
-        let context_range = 3;
-        let start = problematic_token_index.saturating_sub(context_range);
-        let end = (problematic_token_index + context_range + 1).min(tokens.len());
-
-        // Print context with improved formatting
-        println!("Context around the problematic token:");
-        for (i, token) in tokens.iter().enumerate().take(end).skip(start) {
-            if i == problematic_token_index {
-                println!("--> Problematic token: {}", token);
+    importing.pop();
+    files.push(SourceFile {
+        path: file_path.to_string(),
+        canonical,
+        regions: program.regions,
+    });
+}
+
+/// read, tokenise and parse a single file, exiting if there are any errors
+fn parse_file(file_path: &str) -> Program {
+    let source_code = match fs::read_to_string(file_path) {
+        Ok(source_code_str) => {
+            println!("Source code: \n{source_code_str}");
+            source_code_str
+        }
+        Err(error) => {
+            eprintln!("Error reading from file {file_path}:\n{error}");
+            std::process::exit(1);
+        }
+    };
+
+    let (tokens, lex_errors) = Tokeniser::tokenise(&source_code);
+
+    // report all the errors at once, not just the first one
+    if !lex_errors.is_empty() {
+        eprintln!();
+        for error in &lex_errors {
+            handle_lex_error(file_path, &source_code, error);
+        }
+        std::process::exit(1);
+    }
+
+    println!("\n3. Tokens:");
+    for token in &tokens {
+        print!("{} ", token.token.fmt_type_and_value());
+    }
+    println!();
+
+    // doc comments aren't tokens, give them to the parser separately, indexed
+    // by where the token they are attached to starts
+    let doc_comments = tokens
+        .iter()
+        .filter(|t| !t.doc_comments.is_empty())
+        .map(|t| (t.span.start, t.doc_comments.join("\n")))
+        .collect::<HashMap<_, _>>();
+
+    // feed the tokens straight into the parser, along with where they start
+    // and end in the source code
+    let token_stream = tokens
+        .into_iter()
+        .map(|t| Ok((t.span.start, t.token, t.span.end)));
+
+    let program = match ProgramParser::new().parse(&doc_comments, token_stream) {
+        Ok(program) => program,
+        Err(e) => {
+            handle_ast_error(file_path, &source_code, e);
+            std::process::exit(1);
+        }
+    };
+
+    println!("Parsed AST: {:#?}", program);
+
+    program
+}
+
+/// the name of the python package for a file with several regions, which is
+/// the file name without any characters that can't be in an identifier
+fn package_name(file_path: &str) -> String {
+    let file_name = Path::new(file_path)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    file_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
             } else {
-                println!("    Token {}: {}", i, token);
+                '_'
             }
+        })
+        .collect()
+}
+
+fn handle_ast_error(file_path: &str, source_code: &str, e: ParseError<Position, Token, String>) {
+    let (location, message) = match &e {
+        ParseError::InvalidToken { location } => (*location, "invalid token".to_string()),
+        ParseError::UnrecognizedEof { location, expected } => (
+            *location,
+            format!(
+                "unexpected end of file, expected one of: {}",
+                expected.join(", ")
+            ),
+        ),
+        ParseError::UnrecognizedToken {
+            token: (location, token, _),
+            expected,
+        } => (
+            *location,
+            format!(
+                "unexpected token {}, expected one of: {}",
+                token.fmt_type_and_value(),
+                expected.join(", ")
+            ),
+        ),
+        ParseError::ExtraToken {
+            token: (location, token, _),
+        } => (
+            *location,
+            format!("extra token {}", token.fmt_type_and_value()),
+        ),
+        ParseError::User { error } => {
+            // user errors already contain their location
+            eprintln!("Error parsing file {file_path}:{error}");
+            return;
         }
-    }
+    };
+
+    eprintln!(
+        "Error parsing file {file_path}:{}:{}: {message}",
+        location.line, location.col
+    );
+    show_error_location(source_code, location, 1);
+}
+
+fn handle_lex_error(file_path: &str, source_code: &str, error: &LexError) {
+    eprintln!(
+        "Error parsing file {file_path}:{}: {}",
+        error.span, error.message
+    );
+    show_error_location(source_code, error.span.start, error.text.chars().count());
+}
+
+/// show the line of code an error is on, and underline the `len` characters
+/// of the error, starting at `location`
+fn show_error_location(source_code: &str, location: Position, len: usize) {
+    let Some(line) = source_code.lines().nth(location.line.saturating_sub(1)) else {
+        return;
+    };
+
+    // don't underline past the end of the line
+    let col = location.col.saturating_sub(1);
+    let len = len.min(line.chars().count().saturating_sub(col)).max(1);
+
+    eprintln!("{:>4} | {line}", location.line);
+    eprintln!("     | {}{}", " ".repeat(col), "^".repeat(len));
 }
@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// A position in the source code.
+///
+/// The offset is in bytes from the start of the file, and the line and
+/// column are 1-indexed (the column counts characters, not bytes).
+/// The default position (all zeros) is used for nodes that were generated by
+/// the compiler and don't exist in the source code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, col: usize) -> Self {
+        Self { offset, line, col }
+    }
+}
+
+/// The range of source code a token or AST node was parsed from.
+/// `start` is inclusive and `end` is exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// Whether this span points to actual source code, or was made up by
+    /// the compiler.
+    pub fn is_known(&self) -> bool {
+        self.start.line != 0
+    }
+}
+
+/// Spans are displayed as `line:col` of their start, so that they can be
+/// appended to the filename in error messages.
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_known() {
+            write!(f, "{}:{}", self.start.line, self.start.col)
+        } else {
+            write!(f, "<generated>")
+        }
+    }
+}
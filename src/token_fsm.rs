@@ -1,8 +1,9 @@
 use strum::IntoEnumIterator;
 
-use crate::{Keyword, Operator, Token};
+use crate::span::{Position, Span};
+use crate::{Keyword, Operator, SpannedToken, Token};
 
-/// The three states in our tokeniser FSM.
+/// The states in our tokeniser FSM.
 ///
 /// Our language has been designed in such a way that at no point will the FSM
 /// be in a non-accepting state, but then with more characters will transition
@@ -10,15 +11,19 @@ use crate::{Keyword, Operator, Token};
 /// So if the tokeniser enters the Error state, we already know that no
 /// combination of characters afterwards will result in a valid token.
 ///
-/// The only exception to the above is the empty state, before the first
-/// character. Although this technically just results in no tokens being added
-/// to the list, not a parsing error.
+/// The exceptions to the above are the empty state, before the first
+/// character (although this technically just results in no tokens being added
+/// to the list, not a parsing error), the string literal states, which
+/// only become accepting once the closing quote is read, and the comment
+/// states, which never produce a token.
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokeniserState {
     /// The starting state, no characters have been read.
     Start,
 
     /// The characters do not and will not lead to any valid token.
+    /// Contains what went wrong. The rest of the characters until the next
+    /// separator are skipped, then tokenising carries on from there.
     Error(String),
 
     /// This FSM is in an accepting state, and if all the token's characters
@@ -29,6 +34,73 @@ pub enum TokeniserState {
     /// of different tokens as the variant data.
     /// Handling all types of different tokens happens in the delta() function.
     Accepting(Token),
+
+    /// Inside a string literal, the closing quote hasn't been read yet.
+    /// Contains the characters of the string so far, with escape sequences
+    /// already decoded.
+    InString(String),
+
+    /// Inside a string literal, just after a backslash.
+    InStringEscape(String),
+
+    /// Inside a string literal, in a `\xNN` escape sequence.
+    /// Contains the string so far, and the hex digits read so far.
+    InStringHexEscape(String, String),
+
+    /// Inside a string literal with an invalid escape sequence. Contains what
+    /// went wrong. The rest of the string is skipped, so that its contents
+    /// aren't tokenised as code.
+    InInvalidString(String),
+
+    /// Just read `//`, could be a line comment or a doc comment.
+    CommentStart,
+
+    /// Inside a `//` line comment, which is ignored until the end of the line.
+    LineComment,
+
+    /// Inside a `///` doc comment. Contains the text of the comment so far.
+    DocComment(String),
+
+    /// The characters so far are the start of an operator, but not an
+    /// operator on their own, e.g. the first `&` of `&&`.
+    OperatorPrefix(String),
+
+    /// Inside a `/* */` block comment. Block comments can be nested, so this
+    /// contains how many of them we are in.
+    BlockComment(usize),
+
+    /// Inside a block comment, just after a `*` (could be the start of `*/`).
+    BlockCommentStar(usize),
+
+    /// Inside a block comment, just after a `/` (could be the start of `/*`).
+    BlockCommentSlash(usize),
+}
+
+impl TokeniserState {
+    /// Whether the FSM is in the middle of a string literal, comment or
+    /// float, and this character is part of it, instead of separating tokens like
+    /// whitespace and special characters usually do.
+    fn ignores_separator(&self, c: char) -> bool {
+        match self {
+            TokeniserState::InString(_)
+            | TokeniserState::InStringEscape(_)
+            | TokeniserState::InStringHexEscape(_, _)
+            | TokeniserState::InInvalidString(_)
+            | TokeniserState::BlockComment(_)
+            | TokeniserState::BlockCommentStar(_)
+            | TokeniserState::BlockCommentSlash(_) => true,
+
+            // line comments end at the end of the line
+            TokeniserState::CommentStart
+            | TokeniserState::LineComment
+            | TokeniserState::DocComment(_) => c != '\n',
+
+            // the decimal point in a float, e.g. `1.5`
+            TokeniserState::Accepting(Token::Number(n)) => c == '.' && !n.contains('.'),
+
+            _ => false,
+        }
+    }
 }
 
 /// Convenience: define a conversion function from a Token to a TokeniserState.
@@ -38,32 +110,67 @@ impl From<Token> for TokeniserState {
     }
 }
 
+/// An error found while tokenising, e.g. an invalid character.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    /// What went wrong.
+    pub message: String,
+    /// The code that couldn't be tokenised.
+    pub text: String,
+    pub span: Span,
+}
+
+impl LexError {
+    /// Make an error for the code in `s` from `start` to `end`.
+    fn new(message: impl Into<String>, s: &str, start: Position, end: Position) -> Self {
+        Self {
+            message: message.into(),
+            text: s[start.offset..end.offset].to_string(),
+            span: Span::new(start, end),
+        }
+    }
+}
+
 pub struct Tokeniser;
 
 impl Tokeniser {
     /// Tokenise some code into a list of tokens.
     ///
+    /// Tokenising doesn't stop at the first error: the code up to the next
+    /// separator is skipped, and tokenising carries on from there. That way
+    /// all the errors in a file can be reported at once.
+    ///
     /// # Arguments
-    /// filename: the name of the file being tokenised. Only passed to make
-    ///           error messages more informative.
     /// s: the code to tokenise.
     ///
     /// # Returns
-    /// A list of tokens if the code was successfully tokenised, or an error
-    /// message containing the filename, line number, column number, and the
-    /// characters that caused the error.
-    pub fn tokenise(filename: &str, s: &str) -> Result<Vec<Token>, String> {
+    /// A list of tokens, each with the span of code it was read from and the
+    /// doc comments just before it, and a list of all the errors found.
+    /// The code was successfully tokenised if there are no errors.
+    /// Other comments are skipped.
+    pub fn tokenise(s: &str) -> (Vec<SpannedToken>, Vec<LexError>) {
         let mut tokens = vec![];
+        let mut errors = vec![];
+
+        // the doc comments read since the last token, they will be attached
+        // to the next one
+        let mut doc_comments = vec![];
 
         // these variables will be incremented as we iterate through the code
         let mut curr_line = 1;
         let mut curr_col = 1;
 
+        // where the token currently being parsed started
+        let mut token_start = Position::default();
+
         // the current state of the tokeniser FSM, will change over the
         // course of the iteration
         let mut state = TokeniserState::Start;
 
-        for c in s.chars() {
+        for (offset, c) in s.char_indices() {
+            // the position of the current character
+            let pos = Position::new(offset, curr_line, curr_col);
+
             // increment the line/column counters
             if c == '\n' {
                 curr_line += 1;
@@ -73,48 +180,111 @@ impl Tokeniser {
             }
 
             // check if this char is the start of a new token
-            if Self::is_token_separator(c) {
+            if !state.ignores_separator(c) && Self::is_token_separator(c) {
                 // the current token is a "token separator", which indicates the
                 // end of the current token and the start of a new one.
 
-                // check if we are in the accepting state
-                // i.e. we just parsed a token
-                if let TokeniserState::Accepting(token) = state {
-                    tokens.push(token);
+                match std::mem::replace(&mut state, TokeniserState::Start) {
+                    // we were in the accepting state, i.e. we just parsed a
+                    // token
+                    TokeniserState::Accepting(token) => {
+                        let span = Span::new(token_start, pos);
+                        let doc_comments = std::mem::take(&mut doc_comments);
+                        tokens.push(SpannedToken::new(token, span, doc_comments));
+                    }
+
+                    // we just finished a doc comment
+                    TokeniserState::DocComment(text) => doc_comments.push(text),
+
+                    // we skipped to the end of some invalid code
+                    TokeniserState::Error(message) => {
+                        errors.push(LexError::new(message, s, token_start, pos));
+                    }
+
+                    // e.g. a single `&`
+                    TokeniserState::OperatorPrefix(_) => {
+                        errors.push(LexError::new("incomplete operator", s, token_start, pos));
+                    }
 
-                    state = TokeniserState::Start;
+                    _ => {}
                 }
 
                 // if the separator is not a whitespace, then it is a special
                 // token (a dot, a bracket, etc.). Don't forget to add it to the
                 // list of tokens.
                 if let Some(token) = Self::is_special_token(c) {
-                    tokens.push(token);
+                    let end = Position::new(offset + c.len_utf8(), curr_line, curr_col);
+                    let span = Span::new(pos, end);
+                    let doc_comments = std::mem::take(&mut doc_comments);
+                    tokens.push(SpannedToken::new(token, span, doc_comments));
                 }
 
                 continue;
             }
 
+            if state == TokeniserState::Start {
+                token_start = pos;
+            }
+
             // call the FSM's transition function to get the new state
-            state = Self::delta(state, c);
-
-            // check if error after transition
-            if let TokeniserState::Error(e) = &state {
-                // abort parsing
-                return Err(format!(
-                    "Error parsing file {filename}:{curr_line}:{curr_col} while parsing token: {e}",
-                ));
+            let mut new_state = Self::delta(state.clone(), c);
+
+            // operators don't need whitespace around them, e.g. `a+b` or
+            // `!x`. so if this character can't be added to the current token
+            // but one of them is an operator, the current token ends here and
+            // this character starts a new one.
+            if let (TokeniserState::Error(_), TokeniserState::Accepting(token)) =
+                (&new_state, state)
+            {
+                if matches!(token, Token::Operator(_)) || Self::is_operator_char(c) {
+                    let span = Span::new(token_start, pos);
+                    let doc_comments = std::mem::take(&mut doc_comments);
+                    tokens.push(SpannedToken::new(token, span, doc_comments));
+
+                    token_start = pos;
+                    new_state = Self::delta(TokeniserState::Start, c);
+                }
             }
+
+            state = new_state;
         }
 
         // we've reached the end of the code!
+        let end = Position::new(s.len(), curr_line, curr_col);
+
+        let error = match state {
+            // add the last token if it exists
+            TokeniserState::Accepting(token) => {
+                let span = Span::new(token_start, end);
+                tokens.push(SpannedToken::new(token, span, doc_comments));
+                None
+            }
+
+            // the code ends with some invalid code
+            TokeniserState::Error(message) => Some(message),
+
+            // the last string or block comment was never closed
+            TokeniserState::InString(_)
+            | TokeniserState::InStringEscape(_)
+            | TokeniserState::InStringHexEscape(_, _)
+            | TokeniserState::InInvalidString(_) => Some("unterminated string literal".to_string()),
+            TokeniserState::BlockComment(_)
+            | TokeniserState::BlockCommentStar(_)
+            | TokeniserState::BlockCommentSlash(_) => {
+                Some("unterminated block comment".to_string())
+            }
 
-        // add the last token if it exists
-        if let TokeniserState::Accepting(token) = state {
-            tokens.push(token);
+            // the code ends in e.g. a single `&`
+            TokeniserState::OperatorPrefix(_) => Some("incomplete operator".to_string()),
+
+            _ => None,
+        };
+
+        if let Some(message) = error {
+            errors.push(LexError::new(message, s, token_start, end));
         }
 
-        Ok(tokens)
+        (tokens, errors)
     }
 
     /// Check if the character is a character that separates tokens,
@@ -131,8 +301,8 @@ impl Tokeniser {
         let cs = c.to_string();
 
         // the entire string so far, since the start of the FSM.
-        // this variable is used if we can't parse the current character, it is
-        // added to the error message.
+        // this variable is used to check if adding the current character makes
+        // a longer token, e.g. a keyword or a 2 character operator.
         // This variable is the concatenation of the characters until now + the
         // current character.
         // However the "characters until now" part is embedded in the state's
@@ -150,11 +320,15 @@ impl Tokeniser {
                     return Token::Operator(op).into();
                 }
 
+                if Self::is_operator_prefix(&cs) {
+                    return TokeniserState::OperatorPrefix(cs);
+                }
+
                 if let Some(special_token) = Self::is_special_token(c) {
                     return special_token.into();
                 }
 
-                if c.is_alphabetic() {
+                if Self::is_identifier_start(c) {
                     return Token::Identifier(cs).into();
                 }
 
@@ -162,24 +336,137 @@ impl Tokeniser {
                     return Token::Number(cs).into();
                 }
 
+                if c == '"' {
+                    return TokeniserState::InString(String::new());
+                }
+
                 if c.is_whitespace() {
                     // whitespace is ignored
                     return TokeniserState::Start;
                 }
 
                 // error - first letter won't lead to any valid token!
-                // set "full_token_str" to the current character, and let the
-                // end of the function handle the error.
-                full_token_str = cs;
+                // let the end of the function handle the error.
             }
 
             // adding more characters to an error state won't make it better.
-            TokeniserState::Error(e) => return TokeniserState::Error(e + &cs),
+            TokeniserState::Error(e) => return TokeniserState::Error(e),
+
+            TokeniserState::InString(mut s) => {
+                return match c {
+                    '"' => Token::StringLiteral(s).into(),
+                    '\\' => TokeniserState::InStringEscape(s),
+                    _ => {
+                        s.push(c);
+                        TokeniserState::InString(s)
+                    }
+                };
+            }
+
+            TokeniserState::InStringEscape(mut s) => {
+                let escaped = match c {
+                    'n' => '\n',
+                    '"' => '"',
+                    '\\' => '\\',
+                    'x' => return TokeniserState::InStringHexEscape(s, String::new()),
+                    _ => {
+                        return TokeniserState::InInvalidString(format!(
+                            "invalid escape sequence \\{c}"
+                        ))
+                    }
+                };
+
+                s.push(escaped);
+                return TokeniserState::InString(s);
+            }
+
+            TokeniserState::InStringHexEscape(mut s, mut hex) => {
+                if !c.is_ascii_hexdigit() {
+                    return TokeniserState::InInvalidString(format!(
+                        "invalid escape sequence \\x{hex}{c}"
+                    ));
+                }
+
+                hex.push(c);
+                if hex.len() < 2 {
+                    return TokeniserState::InStringHexEscape(s, hex);
+                }
+
+                // \xNN is the character with code point NN, like in python
+                let byte = u8::from_str_radix(&hex, 16).unwrap();
+                s.push(char::from(byte));
+                return TokeniserState::InString(s);
+            }
+
+            // the error is reported once the string ends
+            TokeniserState::InInvalidString(e) => {
+                return match c {
+                    '"' => TokeniserState::Error(e),
+                    _ => TokeniserState::InInvalidString(e),
+                };
+            }
+
+            TokeniserState::CommentStart => {
+                return match c {
+                    '/' => TokeniserState::DocComment(String::new()),
+                    _ => TokeniserState::LineComment,
+                };
+            }
+
+            TokeniserState::LineComment => return TokeniserState::LineComment,
+
+            TokeniserState::DocComment(mut text) => {
+                // `////` is just a regular comment
+                if text.is_empty() && c == '/' {
+                    return TokeniserState::LineComment;
+                }
+
+                // like rustdoc, ignore the space after `///`
+                if !(text.is_empty() && c == ' ') {
+                    text.push(c);
+                }
+                return TokeniserState::DocComment(text);
+            }
+
+            TokeniserState::OperatorPrefix(s) => {
+                full_token_str = s + &cs;
+
+                if let Some(op) = Self::is_operator(&full_token_str) {
+                    return Token::Operator(op).into();
+                }
+            }
+
+            TokeniserState::BlockComment(depth) => {
+                return match c {
+                    '*' => TokeniserState::BlockCommentStar(depth),
+                    '/' => TokeniserState::BlockCommentSlash(depth),
+                    _ => TokeniserState::BlockComment(depth),
+                };
+            }
+
+            TokeniserState::BlockCommentStar(depth) => {
+                return match c {
+                    // end of the outermost block comment
+                    '/' if depth == 1 => TokeniserState::Start,
+                    // end of a nested block comment
+                    '/' => TokeniserState::BlockComment(depth - 1),
+                    '*' => TokeniserState::BlockCommentStar(depth),
+                    _ => TokeniserState::BlockComment(depth),
+                };
+            }
+
+            TokeniserState::BlockCommentSlash(depth) => {
+                return match c {
+                    // start of a nested block comment
+                    '*' => TokeniserState::BlockComment(depth + 1),
+                    '/' => TokeniserState::BlockCommentSlash(depth),
+                    _ => TokeniserState::BlockComment(depth),
+                };
+            }
 
             TokeniserState::Accepting(token) => match token {
                 // the characters so far (before this one) form a valid token.
                 // but what token?
-
                 Token::Identifier(s) => {
                     // the characters so far form a valid identifier
                     full_token_str = s + &cs;
@@ -188,7 +475,7 @@ impl Tokeniser {
                         return Token::Keyword(kw).into();
                     }
 
-                    if c.is_alphanumeric() {
+                    if Self::is_identifier_continue(c) {
                         return Token::Identifier(full_token_str).into();
                     }
                 }
@@ -201,12 +488,17 @@ impl Tokeniser {
                         return Token::Operator(new_op).into();
                     }
 
-                    // edge case: for the string "-1", the parser will first
-                    // think it's a minus operator, but then realise it's a
-                    // number.
-                    if op == Operator::Minus && c.is_ascii_digit() {
-                        return Token::Number(full_token_str).into();
+                    // `//` and `/*` start comments, not operators
+                    if op == Operator::Div && c == '/' {
+                        return TokeniserState::CommentStart;
+                    }
+                    if op == Operator::Div && c == '*' {
+                        return TokeniserState::BlockComment(1);
                     }
+
+                    // note: `-` is always an operator, even in `-1`. Whether
+                    // it's a subtraction or a negation depends on the context,
+                    // which is the parser's job.
                 }
 
                 Token::Keyword(s) => {
@@ -215,8 +507,8 @@ impl Tokeniser {
                     if let Some(kw) = Self::is_keyword(&full_token_str) {
                         return Token::Keyword(kw).into();
                     }
-                    
-                    if c.is_alphanumeric() {
+
+                    if Self::is_identifier_continue(c) {
                         return Token::Identifier(full_token_str).into();
                     }
                 }
@@ -233,19 +525,21 @@ impl Tokeniser {
                     }
                 }
 
-                Token::Lcur
+                Token::StringLiteral(_)
+                | Token::Lcur
                 | Token::Rcur
                 | Token::Lpar
                 | Token::Rpar
                 | Token::Semi
                 | Token::Comma
                 | Token::Dot
+                | Token::Colon
                 | Token::Lbrack
                 | Token::Rbrack => {
-                    // none of these are more than 1 char.
+                    // a string ends at its closing quote, and none of the
+                    // others are more than 1 char.
                     // if we were in these states and we added a character,
                     // no valid token would be formed.
-                    return TokeniserState::Error(cs);
                 }
             },
         };
@@ -253,7 +547,24 @@ impl Tokeniser {
         // if we reach this point, we didn't match any state transitions to any
         // valid tokens
         // => error tokenising this character
-        TokeniserState::Error(full_token_str)
+        TokeniserState::Error(format!("unexpected character `{c}`"))
+    }
+
+    /// Can an identifier start with this character?
+    ///
+    /// Identifiers follow Unicode's UAX #31 (like rust and python): they start
+    /// with an XID_Start character or `_`, followed by any number of
+    /// XID_Continue characters. e.g. `my_var`, `_x1` and `café`.
+    /// This is the only place identifiers are defined, the grammar takes the
+    /// Identifier tokens as they are.
+    fn is_identifier_start(c: char) -> bool {
+        c == '_' || unicode_ident::is_xid_start(c)
+    }
+
+    /// Can this character be part of an identifier, after the first one?
+    /// See is_identifier_start().
+    fn is_identifier_continue(c: char) -> bool {
+        unicode_ident::is_xid_continue(c)
     }
 
     /// Is this string a valid keyword, and if so, which one?
@@ -266,6 +577,16 @@ impl Tokeniser {
         Operator::iter().find(|op| op.as_ref() == s)
     }
 
+    /// Is this string the start of an operator? e.g. `&` for `&&`
+    fn is_operator_prefix(s: &str) -> bool {
+        Operator::iter().any(|op| op.as_ref().starts_with(s))
+    }
+
+    /// Is this character part of any operator?
+    fn is_operator_char(c: char) -> bool {
+        Operator::iter().any(|op| op.as_ref().contains(c))
+    }
+
     /// Is this character a special token, and if so, which one?
     fn is_special_token(c: char) -> Option<Token> {
         match c {
@@ -276,6 +597,7 @@ impl Tokeniser {
             ';' => Some(Token::Semi),
             ',' => Some(Token::Comma),
             '.' => Some(Token::Dot),
+            ':' => Some(Token::Colon),
             '[' => Some(Token::Lbrack),
             ']' => Some(Token::Rbrack),
             _ => None,
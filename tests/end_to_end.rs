@@ -0,0 +1,245 @@
+//! compile examples from `example_input_source_code/`, build them into python
+//! modules with the `pybind_test` crate and call them from python. also checks
+//! the errors the compiler reports for bad source code.
+
+use std::{env, fs, path::Path, process::Command, sync::Mutex};
+
+/// examples share a target dir, so only one of them is built at a time
+static BUILDING: Mutex<()> = Mutex::new(());
+
+/// compile an example, build it into the python module `module` and run a
+/// python script that uses it. returns what the script printed.
+fn run_in_python(example: &str, module: &str, script: &str) -> String {
+    let repo = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(example);
+    let crate_dir = dir.join("pybind_test");
+    fs::create_dir_all(crate_dir.join("src")).unwrap();
+
+    // the compiler writes the generated code into pybind_test/src/auto.rs
+    for file in ["Cargo.toml", "Cargo.lock", "src/lib.rs", "src/util.rs"] {
+        let from = repo.join("pybind_test").join(file);
+        if from.exists() {
+            fs::copy(from, crate_dir.join(file)).unwrap();
+        }
+    }
+    let example_path = repo
+        .join("example_input_source_code")
+        .join(format!("{example}.txt"));
+    let compiled = Command::new(env!("CARGO_BIN_EXE_rust-parser"))
+        .arg(example_path)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        compiled.status.success(),
+        "compiling {example} failed:\n{}",
+        String::from_utf8_lossy(&compiled.stderr)
+    );
+
+    // every example shares the target dir, so pyo3 is only built once
+    let building = BUILDING.lock().unwrap_or_else(|err| err.into_inner());
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("pybind_test");
+    let built = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+        .arg("build")
+        .env("CARGO_TARGET_DIR", &target_dir)
+        .current_dir(&crate_dir)
+        .output()
+        .unwrap();
+    assert!(
+        built.status.success(),
+        "building {example} failed:\n{}",
+        String::from_utf8_lossy(&built.stderr)
+    );
+
+    let library = format!(
+        "{}DataManagement{}",
+        env::consts::DLL_PREFIX,
+        env::consts::DLL_SUFFIX
+    );
+    fs::copy(
+        target_dir.join("debug").join(library),
+        dir.join(format!("{module}.so")),
+    )
+    .unwrap();
+    drop(building);
+
+    let ran = Command::new("python3")
+        .args(["-c", script])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        ran.status.success(),
+        "running {example} failed:\n{}",
+        String::from_utf8_lossy(&ran.stderr)
+    );
+    String::from_utf8(ran.stdout).unwrap()
+}
+
+/// compile source code that has an error in it. returns what the compiler
+/// printed to stderr.
+fn compile_error(name: &str, source: &str) -> String {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::create_dir_all(dir.join("pybind_test/src")).unwrap();
+    let source_path = dir.join(format!("{name}.txt"));
+    fs::write(&source_path, source).unwrap();
+
+    let compiled = Command::new(env!("CARGO_BIN_EXE_rust-parser"))
+        .arg(&source_path)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!compiled.status.success(), "compiling {name} should fail");
+    String::from_utf8(compiled.stderr).unwrap()
+}
+
+#[test]
+fn classes_can_be_used_from_python() {
+    let script = "
+from Shapes import Point, furthest, walk
+
+p = walk(3)
+print(p.x, p.y, p.norm())
+
+q = Point(1, 1).move_by(2, 3)
+print(q.x, q.y, furthest([Point(1, 1), q, Point(0, 2)]).norm())
+";
+    assert_eq!(
+        run_in_python("classes", "Shapes", script),
+        "3 6 45\n3 4 25\n"
+    );
+}
+
+#[test]
+fn runtime_errors_can_be_caught() {
+    let script = "
+from Errors import add, divide, first_byte
+
+print(divide(7, 2), divide(7, 0), divide(1.5, 0.0))
+print(add(1, 2), add(1, 'a'), first_byte(2))
+";
+    assert_eq!(
+        run_in_python("exceptions", "Errors", script),
+        "3 division by zero division by zero\n3 can't add freed\n"
+    );
+}
+
+#[test]
+fn names_used_by_generated_code_keep_their_python_names() {
+    let script = "
+import Reserved
+
+b = Reserved.Buffer(3)
+print(Reserved.range(1, 2), getattr(Reserved, 'lambda')(5), b.py(4), b.m, Reserved.Buffer.__name__)
+";
+    assert_eq!(
+        run_in_python("reserved_names", "Reserved", script),
+        "7 7 7 3 Buffer\n"
+    );
+}
+
+#[test]
+fn division_rounds_down_like_in_python() {
+    let script = "
+from Arithmetic import quotient, remainder
+
+for a, b in [(7, 2), (-7, 2), (7, -2), (-7, -2), (-6, 4)]:
+    assert (quotient(a, b), remainder(a, b)) == (a // b, a % b), (a, b)
+for a, b in [(-7.5, 2.0), (7.5, -2.0), (-7, 2.5)]:
+    assert remainder(a, b) == a % b, (a, b)
+print(quotient(-7, 2), remainder(-7, 2))
+";
+    assert_eq!(run_in_python("arithmetic", "Arithmetic", script), "-4 1\n");
+}
+
+#[test]
+fn constant_zero_divisors_are_rejected() {
+    for (divisor, error) in [
+        ("0", "division by zero"),
+        ("0.0", "division by zero"),
+        ("-0.0", "division by zero"),
+        ("(1 - 1)", "division by zero"),
+        ("(2.5 - 2.5)", "division by zero"),
+    ] {
+        let source = format!("region Zero {{ function f(a) {{ return a / {divisor}; }} }}");
+        let stderr = compile_error("zero", &source);
+        assert!(stderr.contains(error), "dividing by {divisor}: {stderr}");
+    }
+
+    let stderr = compile_error("zero", "region Zero { function f(a) { return a % 0.0; } }");
+    assert!(stderr.contains("modulo by zero"), "{stderr}");
+}
+
+#[test]
+fn break_and_continue_are_only_allowed_in_loops() {
+    let source = "region Jumps {
+    function f(x) {
+        if (x) { break; }
+        for y in x { continue; }
+        continue;
+    }
+}
+";
+    let stderr = compile_error("jumps", source);
+    assert!(
+        stderr.contains("jumps.txt:3:18: `break` outside of a loop"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("jumps.txt:5:9: `continue` outside of a loop"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains(":4:"), "{stderr}");
+}
+
+#[test]
+fn array_literals_become_python_lists() {
+    let script = "
+from Lists import first_of_each, floats, ints, mixed, nested
+
+print(ints(), floats(0.5), mixed(b'x'), nested(3), first_of_each(4))
+print([type(x).__name__ for x in ints() + floats(1.0)])
+";
+    assert_eq!(
+        run_in_python("lists", "Lists", script),
+        "[1, 2, 3] [0.5, 2.5, -1.0] [1, 'two', b'x', None] [[3, 4], [], [[6]]] [4, 5]\n\
+         ['int', 'int', 'int', 'float', 'float', 'float']\n"
+    );
+}
+
+#[test]
+fn every_lex_error_is_reported_before_stopping() {
+    let source = "region Lex {
+    function f(a) {
+        let b = a $ 1;
+        let c = \"\\q\";
+        return b c;
+    }
+}
+";
+    let stderr = compile_error("lex", source);
+    assert!(
+        stderr.contains("lex.txt:3:19: unexpected character `$`"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("lex.txt:4:17: invalid escape sequence \\q"),
+        "{stderr}"
+    );
+    // the missing `;` isn't reported, the code isn't parsed at all
+    assert!(!stderr.contains(":5:"), "{stderr}");
+}
+
+#[test]
+fn functions_are_defined_once_per_region() {
+    let source = "region Twice {
+    function f(x) { return x; }
+    function f(x, y) { return x + y; }
+}
+";
+    let stderr = compile_error("twice", source);
+    assert!(
+        stderr.contains("twice.txt:3:5: `f` is already defined in this region"),
+        "{stderr}"
+    );
+}